@@ -1,4 +1,8 @@
+pub mod spectrum;
+
+use crate::libcore::aov::AovSample;
 use crate::libcore::hit::Hittable;
+use crate::libcore::sampler::Sampler;
 use crate::math::{
     clamp, hit_sphere, lerp, random_in_hemisphere, random_in_unit_sphere, random_unit_vector,
     Point3, Ray, Vec3,
@@ -9,20 +13,111 @@ use std::io::{Error, Write};
 
 pub type Color<T> = Vec3<T>;
 
-pub fn ray_color(r: &Ray, world: &dyn Hittable, depth: usize) -> Color<f64> {
+pub fn ray_color(
+    r: &Ray,
+    world: &dyn Hittable,
+    depth: usize,
+    sampler: &mut dyn Sampler,
+) -> Color<f64> {
     if depth <= 0 {
         return Color::new();
     }
 
-    match world.hit(r, 0.001, f64::MAX) {
+    let surface = world.hit(r, 0.001, f64::MAX);
+    let t_max = surface.as_ref().map_or(f64::MAX, |rec| rec.t);
+    match world.sample_medium(r, 0.001, t_max, sampler).or(surface) {
         Some(rec) => {
-            if let Some(sc_rec) = rec.material.scatter(r, &rec) {
-                return sc_rec.attenuation * ray_color(&sc_rec.scattered, world, depth - 1);
+            let emitted = rec.material.emitted();
+            if let Some(sc_rec) = rec.material.scatter(r, &rec, sampler) {
+                return emitted
+                    + sc_rec.attenuation * ray_color(&sc_rec.scattered, world, depth - 1, sampler);
             }
-            return Color::new();
+            return emitted;
         }
         None => (),
     };
+    background(r)
+}
+
+/// Same path tracing as `ray_color` but keeps every contribution
+/// apart so it can be written to its own render pass. Rays carrying
+/// a wavelength are traced spectrally, their contributions being
+/// converted back to RGB.
+pub fn ray_color_aov(
+    r: &Ray,
+    world: &dyn Hittable,
+    depth: usize,
+    sampler: &mut dyn Sampler,
+) -> AovSample {
+    let mut sample = AovSample::new();
+    let mut ray = *r;
+    let mut throughput = Color::with_values(1.0, 1.0, 1.0);
+    let mut specular = false;
+
+    let wavelengths = r.wavelength.map(spectrum::hero_wavelengths);
+    let mut single_wavelength = false;
+    let spectral = |c: Color<f64>| match &wavelengths {
+        Some(w) => spectrum::upsample(&c, w),
+        None => c,
+    };
+    let to_rgb = |c: Color<f64>| match &wavelengths {
+        Some(w) => spectrum::spectrum_to_rgb(&c, w),
+        None => c,
+    };
+
+    for bounce in 0..depth {
+        let surface = world.hit(&ray, 0.001, f64::MAX);
+        let t_max = surface.as_ref().map_or(f64::MAX, |rec| rec.t);
+        let (rec, in_medium) = match (world.sample_medium(&ray, 0.001, t_max, sampler), surface) {
+            (Some(rec), _) => (rec, true),
+            (None, Some(rec)) => (rec, false),
+            (None, None) => {
+                sample.add_radiance(
+                    to_rgb(throughput * spectral(background(&ray))),
+                    bounce,
+                    specular,
+                );
+                break;
+            }
+        };
+        // Media have no surface to fill the geometric passes with
+        if bounce == 0 && !in_medium {
+            sample.normal = rec.normal;
+            sample.depth = Some(rec.t * ray.direction.length());
+            sample.object_id = Some(rec.object_id);
+        }
+        sample.add_radiance(
+            to_rgb(throughput * spectral(rec.material.emitted())),
+            bounce,
+            specular,
+        );
+
+        match rec.material.scatter(&ray, &rec, sampler) {
+            Some(sc_rec) => {
+                if bounce == 0 {
+                    sample.albedo = sc_rec.attenuation;
+                    specular = sc_rec.specular;
+                }
+                throughput = throughput * spectral(sc_rec.attenuation);
+                if wavelengths.is_some()
+                    && sc_rec.scattered.wavelength.is_some()
+                    && !single_wavelength
+                {
+                    // The other wavelengths would have scattered elsewhere,
+                    // the hero one now stands for all three
+                    throughput = Color::with_values(3.0 * throughput.x(), 0.0, 0.0);
+                    single_wavelength = true;
+                }
+                ray = sc_rec.scattered.with_wavelength(r.wavelength);
+            }
+            None => break,
+        }
+    }
+    sample
+}
+
+/// Radiance coming from the sky when a ray escapes the scene
+pub fn background(r: &Ray) -> Color<f64> {
     let unit_direction = r.direction.unit_vec();
     let t = 0.5 * (unit_direction.y() + 1.0);
 
@@ -33,6 +128,12 @@ pub fn ray_color(r: &Ray, world: &dyn Hittable, depth: usize) -> Color<f64> {
     )
 }
 
+/// Relative luminance of a linear sRGB color
+#[inline]
+pub fn luminance(c: &Color<f64>) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
 pub fn write_color<W: Write>(f: &mut W, c: &Color<u8>) -> Result<(), Error> {
     f.write_fmt(format_args!("{:?} {:?} {:?}\n", c.x(), c.y(), c.z()))
 }
@@ -0,0 +1,143 @@
+//! Spectral rendering helpers: paths carry the radiance of three
+//! wavelengths in the channels of a `Color`, a hero wavelength and
+//! two others evenly spread over the visible range (Wilkie et al. 2014)
+
+use super::Color;
+use crate::math::{clamp, Vec3};
+
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+/// Integral of the y matching function over the visible range
+const CIE_Y_INTEGRAL: f64 = 106.919_734_6;
+
+/// Linear sRGB of an equal energy spectrum, divided out
+/// so that white surfaces stay white
+const WHITE: [f64; 3] = [1.200_536_3, 0.949_666_4, 0.907_828_7];
+
+/// Weights of the red, green and blue basis spectra giving back
+/// a linear sRGB color once integrated
+const RGB_TO_BASIS: [[f64; 3]; 3] = [
+    [1.026_379_400, -0.022_169_801, -0.004_209_600],
+    [0.004_452_539, 0.962_842_993, 0.032_704_468],
+    [0.024_959_207, 0.034_217_606, 0.940_823_187],
+];
+
+/// Uniform wavelength of the visible range for the sample `u`
+pub fn sample_wavelength(u: f64) -> f64 {
+    LAMBDA_MIN + u * (LAMBDA_MAX - LAMBDA_MIN)
+}
+
+/// The `hero` wavelength followed by the two wavelengths
+/// a third and two thirds of the range away
+pub fn hero_wavelengths(hero: f64) -> [f64; 3] {
+    let range = LAMBDA_MAX - LAMBDA_MIN;
+    let rotate = |k: f64| LAMBDA_MIN + ((hero - LAMBDA_MIN) / range + k / 3.0).fract() * range;
+    [rotate(0.0), rotate(1.0), rotate(2.0)]
+}
+
+/// CIE 1931 color matching functions, multi-lobe fit of Wyman et al. 2013
+pub fn cie_xyz(lambda: f64) -> Vec3<f64> {
+    let g = |mu: f64, s1: f64, s2: f64| {
+        let t = (lambda - mu) / if lambda < mu { s1 } else { s2 };
+        (-0.5 * t * t).exp()
+    };
+    Vec3::with_values(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+pub fn xyz_to_srgb(xyz: &Vec3<f64>) -> Color<f64> {
+    Color::with_values(
+        3.240_454_2 * xyz.x() - 1.537_138_5 * xyz.y() - 0.498_531_4 * xyz.z(),
+        -0.969_266_0 * xyz.x() + 1.876_010_8 * xyz.y() + 0.041_556_0 * xyz.z(),
+        0.055_643_4 * xyz.x() - 0.204_025_9 * xyz.y() + 1.057_225_2 * xyz.z(),
+    )
+}
+
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = clamp((x - edge0) / (edge1 - edge0), 0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Value at `lambda` of a smooth spectrum whose color is `c`. The red,
+/// green and blue basis spectra sum to one, white staying flat.
+pub fn rgb_to_spectrum(c: &Color<f64>, lambda: f64) -> f64 {
+    let blue = 1.0 - smoothstep(470.0, 510.0, lambda);
+    let red = smoothstep(570.0, 610.0, lambda);
+    let green = 1.0 - blue - red;
+    let weight = |row: &[f64; 3]| row[0] * c.x() + row[1] * c.y() + row[2] * c.z();
+    let value = weight(&RGB_TO_BASIS[0]) * red
+        + weight(&RGB_TO_BASIS[1]) * green
+        + weight(&RGB_TO_BASIS[2]) * blue;
+    value.max(0.0)
+}
+
+/// Values of the spectrum of `c` at the three `wavelengths`
+pub fn upsample(c: &Color<f64>, wavelengths: &[f64; 3]) -> Color<f64> {
+    Color::with_values(
+        rgb_to_spectrum(c, wavelengths[0]),
+        rgb_to_spectrum(c, wavelengths[1]),
+        rgb_to_spectrum(c, wavelengths[2]),
+    )
+}
+
+/// Linear sRGB estimate of a spectrum known at the three uniformly
+/// sampled `wavelengths`
+pub fn spectrum_to_rgb(values: &Color<f64>, wavelengths: &[f64; 3]) -> Color<f64> {
+    let mut xyz = Vec3::new();
+    for (i, lambda) in wavelengths.iter().enumerate() {
+        xyz = xyz + cie_xyz(*lambda) * values[i];
+    }
+    let scale = (LAMBDA_MAX - LAMBDA_MIN) / (3.0 * CIE_Y_INTEGRAL);
+    let rgb = xyz_to_srgb(&(xyz * scale));
+    Color::with_values(rgb.x() / WHITE[0], rgb.y() / WHITE[1], rgb.z() / WHITE[2])
+}
+
+/// Linear sRGB of the spectrum `f`, integrated with `samples`
+/// wavelengths evenly spread over the visible range
+pub fn integrate_spectrum<F: Fn(f64) -> f64>(f: F, samples: usize) -> Color<f64> {
+    let step = (LAMBDA_MAX - LAMBDA_MIN) / samples as f64;
+    let mut xyz = Vec3::new();
+    for i in 0..samples {
+        let lambda = LAMBDA_MIN + (i as f64 + 0.5) * step;
+        xyz = xyz + cie_xyz(lambda) * f(lambda);
+    }
+    let rgb = xyz_to_srgb(&(xyz * (step / CIE_Y_INTEGRAL)));
+    Color::with_values(rgb.x() / WHITE[0], rgb.y() / WHITE[1], rgb.z() / WHITE[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hero_wavelengths, rgb_to_spectrum, sample_wavelength, spectrum_to_rgb, upsample};
+    use crate::color::Color;
+
+    /// Averages the conversion of spectra over many wavelength samples
+    fn round_trip(c: &Color<f64>) -> Color<f64> {
+        let n = 4000;
+        let mut sum = Color::new();
+        for i in 0..n {
+            let wavelengths = hero_wavelengths(sample_wavelength((i as f64 + 0.5) / n as f64));
+            sum = sum + spectrum_to_rgb(&upsample(c, &wavelengths), &wavelengths);
+        }
+        sum / n as f64
+    }
+
+    #[test]
+    fn colors_survive_the_spectral_round_trip() {
+        for c in [
+            Color::with_values(1.0, 1.0, 1.0),
+            Color::with_values(0.8, 0.5, 0.2),
+            Color::with_values(0.1, 0.2, 0.9),
+        ]
+        .iter()
+        {
+            let back = round_trip(c);
+            assert!((back - *c).length() < 0.01, "{:?} {:?}", c, back);
+        }
+        // White stays flat
+        assert!((rgb_to_spectrum(&Color::with_values(1.0, 1.0, 1.0), 450.0) - 1.0).abs() < 1e-6);
+    }
+}
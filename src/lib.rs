@@ -3,14 +3,26 @@ pub mod libcore;
 pub mod math;
 pub mod utility;
 use libcore::material::Dielectric;
+use libcore::material::{HenyeyGreenstein, Isotropic};
 use libcore::material::Lambertian;
 use libcore::material::Material;
 use libcore::material::Metallic;
 
-use color::{ray_color, transform_and_write_color, transform_to_u8_color, write_color, Color};
-use libcore::camera::Camera;
+use color::spectrum::sample_wavelength;
+use color::{ray_color_aov, transform_to_u8_color, write_color, Color};
+use libcore::adaptive::AdaptiveSampling;
+use libcore::aov::{AovLayer, AovPixel, AovSample, FrameBuffer};
+use libcore::camera::{
+    compose_anaglyph, Camera, CameraAnimation, CameraPose, CubemapCamera, EquirectangularCamera,
+    FisheyeCamera, LensSystemCamera, OrthographicCamera, PerspectiveCamera, Shutter,
+    ShutterCamera, StereoCamera, StereoLayout,
+};
+use libcore::denoise::{denoise, DenoiseSettings};
+use libcore::film::FilmTile;
 use libcore::hit::Hittable;
 use libcore::hittable_list::HittableList;
+use libcore::medium::{GlobalFog, GridMedium};
+use math::moving_sphere::MovingSphere;
 use math::sphere::Sphere;
 use math::Point3;
 use math::Ray;
@@ -20,17 +32,18 @@ use std::io::Result;
 use std::io::Write;
 use std::str::FromStr;
 use std::sync::Arc;
-use utility::{parse, ImageFormat, ThreadData};
+use utility::{parse, CameraKind, FogSettings, ImageFormat, ThreadData, UserData};
 
 extern crate image;
-use image::png::PNGEncoder;
+use image::png::PngEncoder;
 use image::ColorType;
 extern crate rayon;
 use rayon::prelude::*;
 
 extern crate rand;
-use rand::random;
+use rand::rngs::StdRng;
 use rand::Rng;
+use rand::SeedableRng;
 
 use std::time::Instant;
 
@@ -41,7 +54,7 @@ pub fn main() {
 
     let user_data = parse();
 
-    match &user_data {
+    match &user_data.format {
         ImageFormat::PNG { width, filename } => {
             image_width = width;
             out_file = filename;
@@ -58,38 +71,40 @@ pub fn main() {
         }
     }
 
-    let aspect_ratio = 3.0 / 2.0;
-    let image_height = *image_width as f64 / aspect_ratio;
-    let image_width = *image_width;
+    let aspect_ratio = user_data.camera.aspect_ratio().unwrap_or(3.0 / 2.0);
+    let eye_size = user_data.camera.image_size(*image_width);
+    // Both eyes get the requested size
+    let (image_width, image_height) = match &user_data.stereo {
+        Some(stereo) => stereo.layout.render_size(eye_size.0, eye_size.1),
+        None => eye_size,
+    };
     let samples_per_pixel = 500;
     let max_depth = 50;
 
     // Camera
 
-    let lookfrom = Point3::with_values(13., 2., 3.);
-    let lookat = Point3::with_values(0., 0., 0.);
     let vup = Vec3::with_values(0., 1., 0.);
-    let dist_to_focus = 10.;
-    let aperture = 0.1;
-    let vfov = 20.;
-
-    let cam = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        vfov,
-        aspect_ratio,
-        aperture,
-        dist_to_focus,
-    );
-
-    let thread_shared = ThreadData {
-        camera: &cam,
-        image_height: image_height as usize,
-        image_width,
-        aspect_ratio,
-        samples_per_pixel,
-        max_depth,
+    let default_pose = CameraPose {
+        lookfrom: Point3::with_values(13., 2., 3.),
+        lookat: Point3::with_values(0., 0., 0.),
+        vfov: 20.,
+        focus_dist: 10.,
+    };
+    let animation = match (&user_data.camera_path, user_data.turntable) {
+        (Some(path), _) => Some(CameraAnimation::Path(path.clone())),
+        (None, Some(frames)) => Some(CameraAnimation::Turntable {
+            start: default_pose,
+            frames,
+        }),
+        (None, None) => None,
+    };
+    // An explicit shutter interval wins over the physical shutter speed
+    let shutter = user_data
+        .shutter
+        .or_else(|| user_data.physical.map(|physical| physical.shutter()));
+    let exposure = match &user_data.physical {
+        Some(physical) => physical.exposure() * user_data.luminance_unit,
+        None => 1.0,
     };
 
     // World
@@ -146,32 +161,242 @@ pub fn main() {
 
     // let _ = func(&thread_shared, &out_file,Box::new(world));
     // let _ = generate_as_ppm(&thread_shared, &out_file, Arc::new(world));
-    let world = random_scene();
+    let fog = user_data.fog.unwrap_or(FogSettings {
+        density: 0.0,
+        height: 0.0,
+        anisotropy: 0.0,
+    });
+    let fog_phase = Arc::new(HenyeyGreenstein::new(
+        Color::with_values(1.0, 1.0, 1.0),
+        fog.anisotropy,
+    ));
+    let mut scene = random_scene(user_data.seed, shutter.is_some());
+    if let Some(volume) = &user_data.volume {
+        scene.add(Arc::new(GridMedium::new(
+            volume.grid.clone(),
+            volume.bounds,
+            volume.density,
+            Arc::new(Isotropic::new(Color::with_values(1.0, 1.0, 1.0))),
+        )));
+    }
+    let world = GlobalFog::new(
+        scene,
+        fog.density,
+        fog.height,
+        fog_phase,
+    );
 
     let now = Instant::now();
 
-    match user_data {
-        ImageFormat::PPM { .. } => {
-            let _ = generate_as_ppm(&thread_shared, &out_file, &world);
-        }
-        ImageFormat::PNG { .. } => {
-            let _ = generate_as_png(&thread_shared, &out_file, &world);
+    let numbered = user_data.frames.is_some() || animation.is_some();
+    let (first, last) = match (user_data.frames, &animation) {
+        (Some(frames), _) => frames,
+        (None, Some(animation)) => animation.frames(),
+        (None, None) => (0, 0),
+    };
+    for frame in first..=last {
+        let pose = match &animation {
+            Some(animation) => animation.pose(frame as f64),
+            None => default_pose,
+        };
+        // Every frame exposes its own slice of the scene time
+        let time = frame as f64 / user_data.fps;
+        let shutter = shutter.map(|s| Shutter::new(s.open + time, s.close + time));
+        let cam = build_camera(&user_data, &pose, vup, aspect_ratio, eye_size, shutter);
+
+        let (output, heatmap) = match numbered {
+            true => (
+                frame_path(out_file, frame),
+                user_data.heatmap.as_ref().map(|h| frame_path(h, frame)),
+            ),
+            false => (out_file.clone(), user_data.heatmap.clone()),
+        };
+
+        let thread_shared = ThreadData {
+            camera: cam.as_ref(),
+            image_height,
+            image_width,
+            aspect_ratio,
+            samples_per_pixel,
+            max_depth,
+            aov: user_data.aov,
+            denoise: user_data.denoise,
+            adaptive: user_data.adaptive_threshold.map(|threshold| {
+                AdaptiveSampling::new(
+                    user_data.min_samples,
+                    user_data.max_samples.unwrap_or(samples_per_pixel),
+                    threshold,
+                )
+            }),
+            heatmap: heatmap.as_deref(),
+            sampler: user_data.sampler,
+            seed: user_data.seed,
+            filter: user_data.filter,
+            stereo: user_data.stereo.map(|stereo| stereo.layout),
+            exposure,
+            spectral: user_data.spectral,
+        };
+
+        match user_data.format {
+            ImageFormat::PPM { .. } => {
+                let _ = generate_as_ppm(&thread_shared, &output, &world);
+            }
+            ImageFormat::PNG { .. } => {
+                let _ = generate_as_png(&thread_shared, &output, &world);
+            }
+            _ => panic!(),
         }
-        _ => panic!(),
     }
     let elapsed = now.elapsed();
     println!("Done. Elapsed: {:.2?}", elapsed);
 }
 
+/// Camera of `user_data` kind placed at `pose`
+fn build_camera(
+    user_data: &UserData,
+    pose: &CameraPose,
+    vup: Vec3<f64>,
+    aspect_ratio: f64,
+    eye_size: (usize, usize),
+    shutter: Option<Shutter>,
+) -> Box<dyn Camera + Sync> {
+    let lookfrom = pose.lookfrom;
+    let lookat = pose.lookat;
+    let dist_to_focus = pose.focus_dist;
+    let vfov = pose.vfov;
+    let aperture = match &user_data.physical {
+        Some(physical) => physical.aperture_diameter(math::degrees_to_radians(vfov)),
+        None => 0.1,
+    };
+
+    let perspective = PerspectiveCamera::new(
+        lookfrom,
+        lookat,
+        vup,
+        vfov,
+        aspect_ratio,
+        aperture,
+        dist_to_focus,
+    )
+    .with_aperture_shape(user_data.lens.aperture_shape.clone())
+    .with_cat_eye(user_data.lens.cat_eye)
+    .with_vignetting(user_data.lens.vignetting)
+    .with_lens_shift(user_data.lens.shift.0, user_data.lens.shift.1)
+    .with_focus_tilt(user_data.lens.tilt, user_data.lens.swing);
+
+    let cam: Box<dyn Camera + Sync> = match (user_data.camera, user_data.stereo) {
+        (CameraKind::Perspective, Some(stereo)) => Box::new(StereoCamera::perspective(
+            &perspective,
+            stereo.interocular,
+            stereo.convergence,
+            stereo.layout,
+            eye_size,
+        )),
+        (CameraKind::Equirectangular, Some(stereo)) => Box::new(StereoCamera::omnidirectional(
+            lookfrom,
+            lookat,
+            vup,
+            stereo.interocular,
+            stereo.convergence,
+            stereo.layout,
+            eye_size,
+        )),
+        (_, Some(_)) => panic!("Stereo needs a perspective or equirectangular camera"),
+        (CameraKind::Perspective, None) => Box::new(perspective),
+        (CameraKind::Orthographic { view_width }, None) => Box::new(OrthographicCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            view_width,
+            aspect_ratio,
+        )),
+        (CameraKind::Equirectangular, None) => {
+            Box::new(EquirectangularCamera::new(lookfrom, lookat, vup))
+        }
+        (CameraKind::Fisheye { fov, mapping }, None) => Box::new(FisheyeCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            fov,
+            aspect_ratio,
+            mapping,
+        )),
+        (CameraKind::Cubemap, None) => Box::new(CubemapCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            eye_size.0 / 3,
+        )),
+        (CameraKind::LensSystem, None) => Box::new(LensSystemCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            user_data.lens_elements.clone().unwrap(),
+            aspect_ratio,
+            dist_to_focus,
+        )),
+    };
+    match shutter {
+        Some(shutter) => Box::new(ShutterCamera::new(cam, shutter)),
+        None => cam,
+    }
+}
+
+
+/// `path` with the frame number before its extension, img.0001.png
+fn frame_path(path: &str, frame: i64) -> String {
+    match path.rfind('.') {
+        Some(index) if !path[index..].contains('/') => {
+            format!("{}.{:04}{}", &path[..index], frame, &path[index..])
+        }
+        _ => format!("{}.{:04}", path, frame),
+    }
+}
+
 pub fn generate_as_png<T: Hittable + Sync>(
     data: &ThreadData,
     output: &String,
     world: &T,
 ) -> Result<()> {
+    let frame = render_frame(data, world);
+    let (pixels, width) = display_pixels(data, &frame);
+    write_image_png(output, &pixels, (width, frame.height))?;
+    write_frame_extras(data, output, &frame)
+}
+
+/// Beauty pass ready for display and its width, denoised and composed
+/// into an anaglyph when asked for
+fn display_pixels(data: &ThreadData, frame: &FrameBuffer) -> (Vec<Color<u8>>, usize) {
+    let mut beauty = match data.denoise {
+        true => denoise(frame, &DenoiseSettings::default()),
+        false => frame.layer(AovLayer::Beauty),
+    };
+    let mut width = frame.width;
+    if data.stereo == Some(StereoLayout::Anaglyph) {
+        beauty = compose_anaglyph(&beauty, frame.width, frame.height);
+        width /= 2;
+    }
+    let pixels = beauty.iter().map(|c| transform_to_u8_color(c, 1)).collect();
+    (pixels, width)
+}
+
+/// Sampling heatmap and AOV layers written next to the image
+fn write_frame_extras(data: &ThreadData, output: &str, frame: &FrameBuffer) -> Result<()> {
+    if let (Some(adaptive), Some(heatmap)) = (&data.adaptive, data.heatmap) {
+        write_image_png(heatmap, &adaptive.heatmap(frame), (frame.width, frame.height))?;
+    }
+    match data.aov {
+        Some(format) => frame.write_aovs(output, format),
+        None => Ok(()),
+    }
+}
+
+/// Path traces the whole image keeping every render pass
+pub fn render_frame<T: Hittable + Sync>(data: &ThreadData, world: &T) -> FrameBuffer {
     let image_width = data.image_width;
     let image_height = data.image_height;
 
-    let mut pixels = vec![Color::new(); image_width * image_height as usize];
+    let mut frame = FrameBuffer::new(image_width, image_height, data.filter);
     println!(
         "width: {:?} height: {:?} pixels: {:?}",
         image_width,
@@ -196,75 +421,97 @@ pub fn generate_as_png<T: Hittable + Sync>(
     //     });
     // }
 
-    let bands: Vec<(usize, &mut [Color<u8>])> =
-        pixels.chunks_mut(image_width).enumerate().collect();
-    bands.into_par_iter().for_each(|(i, band)| {
-        let top = i;
-        let band_bounds = (image_width, 1);
-        render(band, band_bounds, top, data, world);
-    });
+    let film = &frame.film;
+    let bands: Vec<(usize, &mut [AovPixel])> =
+        frame.pixels.chunks_mut(image_width).enumerate().collect();
+    let tiles: Vec<FilmTile> = bands
+        .into_par_iter()
+        .map(|(i, band)| {
+            let top = i;
+            let band_bounds = (image_width, 1);
+            let mut tile = film.tile(top, band_bounds.1);
+            render(band, &mut tile, band_bounds, top, data, world);
+            tile
+        })
+        .collect();
+    // Neighbouring tiles overlap by the filter radius,
+    // merging in order keeps the sums reproducible
+    for tile in &tiles {
+        frame.film.merge(tile);
+    }
 
-    write_image_png(output, &mut pixels, (image_width, image_height as usize))
+    frame
 }
 
 fn render(
-    pixels: &mut [Color<u8>],
+    pixels: &mut [AovPixel],
+    tile: &mut FilmTile,
     bounds: (usize, usize),
     top: usize,
     data: &ThreadData,
     world: &dyn Hittable,
 ) {
+    let max_samples = match &data.adaptive {
+        Some(adaptive) => adaptive.max_samples,
+        None => data.samples_per_pixel,
+    };
+    let mut sampler = data.sampler.create(max_samples, data.seed);
+
     for j in 0..bounds.1 {
         for i in 0..bounds.0 {
-            let mut pixel_color = Color::new();
+            let pixel = &mut pixels[j * bounds.0 + i];
+
+            loop {
+                let converged = match &data.adaptive {
+                    Some(adaptive) => adaptive.converged(pixel),
+                    None => pixel.samples >= data.samples_per_pixel,
+                };
+                if converged {
+                    break;
+                }
 
-            for _ in 0..data.samples_per_pixel {
-                let u = (i as f64 + random::<f64>()) / ((data.image_width - 1) as f64);
-                let v = ((data.image_height - 1 - (j + top)) as f64 + random::<f64>())
+                sampler.start_pixel_sample((i, j + top), pixel.samples);
+                let (du, dv) = sampler.get_2d();
+                let u = (i as f64 + du) / ((data.image_width - 1) as f64);
+                let v = ((data.image_height - 1 - (j + top)) as f64 + dv)
                     / ((data.image_height - 1) as f64);
 
-                let ray = data.camera.get_ray(u, v);
-                pixel_color += &ray_color(&ray, world, data.max_depth);
+                let mut sample = match data.camera.get_ray(u, v, sampler.as_mut()) {
+                    Some(ray) => {
+                        let ray = match data.spectral {
+                            true => ray.with_wavelength(Some(sample_wavelength(sampler.get_1d()))),
+                            false => ray,
+                        };
+                        ray_color_aov(&ray, world, data.max_depth, sampler.as_mut())
+                    }
+                    None => AovSample::new(),
+                };
+                sample.expose(data.exposure);
+                pixel.add(&sample);
+                tile.add_sample((i as f64 + du, (j + top) as f64 + 1.0 - dv), &sample.beauty);
             }
-
-            pixels[j * bounds.1 + i] = transform_to_u8_color(&pixel_color, data.samples_per_pixel);
         }
     }
 }
 
-pub fn generate_as_ppm(data: &ThreadData, output: &String, world: &dyn Hittable) -> Result<()> {
+pub fn generate_as_ppm<T: Hittable + Sync>(
+    data: &ThreadData,
+    output: &String,
+    world: &T,
+) -> Result<()> {
+    let frame = render_frame(data, world);
+    let (pixels, width) = display_pixels(data, &frame);
     let mut file = match File::create(output) {
         Ok(f) => f,
         Err(err) => return Err(err),
     };
-    let aspect_ratio = data.aspect_ratio;
-    let image_width = data.image_width;
-    let image_height = image_width as f64 / aspect_ratio;
-
-    // println!("P3\n{:?} {:?}\n255", image_width, image_height as u32);
-    file.write_fmt(format_args!(
-        "P3\n{:?} {:?}\n255\n",
-        image_width, image_height as u32
-    ))
-    .expect("Unable to write data");
 
-    for i in (0..(image_height as u64)).rev() {
-        write!(std::io::stderr(), "\rScanlines remaining: {:?} ", i);
-        for j in 0..image_width {
-            let mut pixel_color = Color::<f64>::new();
-            for s in 0..data.samples_per_pixel {
-                let u = (j as f64 + random::<f64>()) / ((image_width - 1) as f64);
-                let v = (i as f64 + random::<f64>()) / (image_height - 1.0);
-                let ray = data.camera.get_ray(u, v);
-
-                pixel_color += &ray_color(&ray, world, data.max_depth);
-            }
-
-            transform_and_write_color(&mut file, &pixel_color, data.samples_per_pixel)
-                .expect("Error writing to stdout")
-        }
+    file.write_fmt(format_args!("P3\n{:?} {:?}\n255\n", width, frame.height))
+        .expect("Unable to write data");
+    for pixel in &pixels {
+        write_color(&mut file, pixel).expect("Error writing to stdout");
     }
-    return Ok(());
+    write_frame_extras(data, output, &frame)
 }
 
 pub fn write_image_png(filename: &str, pixels: &[Color<u8>], bounds: (usize, usize)) -> Result<()> {
@@ -272,7 +519,7 @@ pub fn write_image_png(filename: &str, pixels: &[Color<u8>], bounds: (usize, usi
         Ok(f) => f,
         Err(err) => return Err(err),
     };
-    let encoder = PNGEncoder::new(output);
+    let encoder = PngEncoder::new(output);
     // Flattening a big array takes a lot of time
     // TODO: Optimize this by making the render function
     // directly produce the rgb array
@@ -323,8 +570,11 @@ fn parse_pair<T: FromStr>(s: &str, separator: char) -> Option<(T, T)> {
     }
 }
 
-fn random_scene() -> HittableList<Sphere> {
-    let mut world = HittableList::new();
+/// Spheres scattered around three big ones, the diffuse ones bouncing
+/// when `bouncing` is set for renders with an open shutter
+fn random_scene(seed: u64, bouncing: bool) -> HittableList<dyn Hittable + Send + Sync> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut world: HittableList<dyn Hittable + Send + Sync> = HittableList::new();
     let ground_mat = Arc::new(Lambertian::new(Color::with_values(0.5, 0.5, 0.5)));
     world.add(Arc::new(Sphere::new(
         Point3::with_values(0., -1000., 0.),
@@ -334,21 +584,33 @@ fn random_scene() -> HittableList<Sphere> {
 
     for a in -11..11 {
         for b in -11..11 {
-            let choose_mat = random::<f64>();
+            let choose_mat = rng.gen::<f64>();
             let center = Point3::with_values(
-                (a as f64) + 0.9 * random::<f64>(),
+                (a as f64) + 0.9 * rng.gen::<f64>(),
                 0.2,
-                (b as f64) + 0.9 * random::<f64>(),
+                (b as f64) + 0.9 * rng.gen::<f64>(),
             );
             if (center - Point3::with_values(4., 0.2, 0.)).length() > 0.9 {
                 if choose_mat < 0.8 {
                     // diffuse
-                    let albedo = random::<Color<f64>>() * random::<Color<f64>>();
+                    let albedo = rng.gen::<Color<f64>>() * rng.gen::<Color<f64>>();
                     let sphere_mat = Arc::new(Lambertian::new(albedo));
-                    world.add(Arc::new(Sphere::new(center, 0.2, sphere_mat.clone())));
+                    // Reusing choose_mat for the bounce height keeps
+                    // the scene layout of a seed
+                    let bounce = Vec3::with_values(0., 0.5 * choose_mat / 0.8, 0.);
+                    match bouncing {
+                        true => world.add(Arc::new(MovingSphere::new(
+                            center,
+                            center + bounce,
+                            0.,
+                            1.,
+                            0.2,
+                            sphere_mat.clone(),
+                        ))),
+                        false => world.add(Arc::new(Sphere::new(center, 0.2, sphere_mat.clone()))),
+                    }
                 } else if choose_mat < 0.95 {
                     // metal
-                    let mut rng = rand::thread_rng();
                     let albedo = Color::with_values(
                         rng.gen_range(0., 0.5),
                         rng.gen_range(0., 0.5),
@@ -0,0 +1,74 @@
+use crate::math::{Point3, Ray};
+
+/// Axis aligned bounding box, used by acceleration structures
+/// to skip whole groups of objects missed by a ray
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Point3<f64>,
+    pub max: Point3<f64>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f64>, max: Point3<f64>) -> Self {
+        Aabb { min, max }
+    }
+
+    /// Smallest box containing both `a` and `b`
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Self {
+        Aabb {
+            min: Point3::with_values(
+                f64::min(a.min.x(), b.min.x()),
+                f64::min(a.min.y(), b.min.y()),
+                f64::min(a.min.z(), b.min.z()),
+            ),
+            max: Point3::with_values(
+                f64::max(a.max.x(), b.max.x()),
+                f64::max(a.max.y(), b.max.y()),
+                f64::max(a.max.z(), b.max.z()),
+            ),
+        }
+    }
+
+    /// Slab test, whether the ray crosses the box between `t_min` and `t_max`
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.interval(ray, t_min, t_max).is_some()
+    }
+
+    /// Part of `t_min..t_max` along the ray inside the box
+    pub fn interval(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
+        for axis in 0..3 {
+            let inv_d = 1.0 / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = f64::max(t0, t_min);
+            t_max = f64::min(t1, t_max);
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Aabb;
+    use crate::math::{Point3, Ray, Vec3};
+
+    #[test]
+    fn slab_test() {
+        let aabb = Aabb::new(
+            Point3::with_values(-1.0, -1.0, -1.0),
+            Point3::with_values(1.0, 1.0, 1.0),
+        );
+        let origin = Point3::with_values(0.0, 0.0, -5.0);
+        assert!(aabb.hit(&Ray::new(origin, Vec3::with_values(0.0, 0.0, 1.0)), 0.0, 10.0));
+        assert!(!aabb.hit(&Ray::new(origin, Vec3::with_values(0.0, 0.0, -1.0)), 0.0, 10.0));
+        assert!(!aabb.hit(&Ray::new(origin, Vec3::with_values(1.0, 0.0, 1.0)), 0.0, 10.0));
+        // The box is out of reach
+        assert!(!aabb.hit(&Ray::new(origin, Vec3::with_values(0.0, 0.0, 1.0)), 0.0, 3.0));
+    }
+}
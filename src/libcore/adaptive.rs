@@ -0,0 +1,112 @@
+use super::aov::{AovPixel, FrameBuffer};
+use crate::color::Color;
+use crate::math::{clamp, lerp};
+
+/// Convergence is only checked every few samples, a couple of
+/// lucky samples agreeing with each other shouldn't stop a pixel.
+/// Must be a power of two.
+const CHECK_INTERVAL: usize = 8;
+
+/// Luminance under which the error is no longer measured relatively,
+/// otherwise black pixels would never be considered converged
+const DARK_LUMINANCE: f64 = 0.05;
+
+/// Keeps sampling a pixel only while the estimated error
+/// of its mean luminance is above `threshold`
+#[derive(Clone, Copy, Debug)]
+pub struct AdaptiveSampling {
+    pub min_samples: usize,
+    pub max_samples: usize,
+    /// Maximum relative standard error of the pixel mean
+    pub threshold: f64,
+}
+
+impl AdaptiveSampling {
+    pub fn new(min_samples: usize, max_samples: usize, threshold: f64) -> Self {
+        let min_samples = usize::max(min_samples, 2);
+        AdaptiveSampling {
+            min_samples,
+            max_samples: usize::max(min_samples, max_samples),
+            threshold,
+        }
+    }
+
+    /// Whether the pixel received enough samples
+    pub fn converged(&self, pixel: &AovPixel) -> bool {
+        let n = pixel.samples;
+        if n >= self.max_samples {
+            return true;
+        }
+        if n < self.min_samples || n & (CHECK_INTERVAL - 1) != 0 {
+            return false;
+        }
+        relative_error(pixel) <= self.threshold
+    }
+
+    /// Sample count of every pixel from blue (few) to red (`max_samples`)
+    pub fn heatmap(&self, frame: &FrameBuffer) -> Vec<Color<u8>> {
+        let cold = Color::with_values(0.0, 0.0, 255.0);
+        let warm = Color::with_values(255.0, 255.0, 0.0);
+        let hot = Color::with_values(255.0, 0.0, 0.0);
+        frame
+            .pixels
+            .iter()
+            .map(|p| {
+                let t = clamp(p.samples as f64 / self.max_samples as f64, 0.0, 1.0);
+                let c = match t {
+                    t if t < 0.5 => lerp(&cold, &warm, t * 2.0),
+                    t => lerp(&warm, &hot, t * 2.0 - 1.0),
+                };
+                Color::with_values(c.x() as u8, c.y() as u8, c.z() as u8)
+            })
+            .collect()
+    }
+}
+
+/// Standard error of the mean luminance relative to the mean itself
+pub fn relative_error(pixel: &AovPixel) -> f64 {
+    let standard_error = (pixel.luminance_variance() / pixel.samples as f64).sqrt();
+    standard_error / f64::max(pixel.luminance_mean(), DARK_LUMINANCE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AdaptiveSampling;
+    use crate::color::Color;
+    use crate::libcore::aov::{AovPixel, AovSample};
+
+    /// Samples taken by a pixel whose i-th sample has radiance `radiance(i)`
+    fn sample_count<F: Fn(usize) -> f64>(adaptive: &AdaptiveSampling, radiance: F) -> usize {
+        let mut pixel = AovPixel::new();
+        while !adaptive.converged(&pixel) {
+            let mut sample = AovSample::new();
+            let l = radiance(pixel.samples);
+            sample.beauty = Color::with_values(l, l, l);
+            pixel.add(&sample);
+        }
+        pixel.samples
+    }
+
+    #[test]
+    fn flat_pixels_stop_early() {
+        let adaptive = AdaptiveSampling::new(16, 256, 0.01);
+        assert_eq!(sample_count(&adaptive, |_| 0.5), 16);
+        // Convergence is first checked at the next multiple of 8
+        let adaptive = AdaptiveSampling::new(12, 256, 0.01);
+        assert_eq!(sample_count(&adaptive, |_| 0.5), 16);
+        // At least two samples are needed for a variance, which the
+        // check interval rounds up
+        let adaptive = AdaptiveSampling::new(0, 256, 0.01);
+        assert_eq!(adaptive.min_samples, 2);
+        assert_eq!(sample_count(&adaptive, |_| 0.5), 8);
+    }
+
+    #[test]
+    fn noisy_pixels_use_every_sample() {
+        let adaptive = AdaptiveSampling::new(16, 256, 0.01);
+        assert_eq!(sample_count(&adaptive, |i| (i % 2) as f64), 256);
+        // The maximum never falls under the minimum
+        let adaptive = AdaptiveSampling::new(16, 4, 0.01);
+        assert_eq!(sample_count(&adaptive, |i| (i % 2) as f64), 16);
+    }
+}
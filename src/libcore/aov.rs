@@ -0,0 +1,374 @@
+use super::exr::write_exr;
+use super::film::{Film, Filter};
+use crate::color::{luminance, transform_to_u8_color, Color};
+use crate::math::{clamp, Vec3};
+use std::io::Result;
+use std::path::Path;
+
+/// How the arbitrary output variables are written next to the beauty image
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AovFormat {
+    /// A single multi-layer OpenEXR file
+    Exr,
+    /// One png per layer, named after the beauty file
+    Png,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AovLayer {
+    Beauty,
+    DiffuseDirect,
+    DiffuseIndirect,
+    SpecularDirect,
+    SpecularIndirect,
+    Emission,
+    Albedo,
+    Normal,
+    Depth,
+    ObjectId,
+}
+
+impl AovLayer {
+    pub const ALL: [AovLayer; 10] = [
+        AovLayer::Beauty,
+        AovLayer::DiffuseDirect,
+        AovLayer::DiffuseIndirect,
+        AovLayer::SpecularDirect,
+        AovLayer::SpecularIndirect,
+        AovLayer::Emission,
+        AovLayer::Albedo,
+        AovLayer::Normal,
+        AovLayer::Depth,
+        AovLayer::ObjectId,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AovLayer::Beauty => "beauty",
+            AovLayer::DiffuseDirect => "diffuse_direct",
+            AovLayer::DiffuseIndirect => "diffuse_indirect",
+            AovLayer::SpecularDirect => "specular_direct",
+            AovLayer::SpecularIndirect => "specular_indirect",
+            AovLayer::Emission => "emission",
+            AovLayer::Albedo => "albedo",
+            AovLayer::Normal => "normal",
+            AovLayer::Depth => "depth",
+            AovLayer::ObjectId => "object_id",
+        }
+    }
+
+    /// Channel names as written in the exr file, single channel
+    /// layers only use the first component of their values
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            AovLayer::Normal => &["X", "Y", "Z"],
+            AovLayer::Depth => &["Z"],
+            AovLayer::ObjectId => &["id"],
+            _ => &["R", "G", "B"],
+        }
+    }
+}
+
+/// Everything gathered while tracing a single camera ray
+#[derive(Clone, Copy)]
+pub struct AovSample {
+    pub beauty: Color<f64>,
+    pub diffuse_direct: Color<f64>,
+    pub diffuse_indirect: Color<f64>,
+    pub specular_direct: Color<f64>,
+    pub specular_indirect: Color<f64>,
+    pub emission: Color<f64>,
+    pub albedo: Color<f64>,
+    pub normal: Vec3<f64>,
+    pub depth: Option<f64>,
+    pub object_id: Option<usize>,
+}
+
+impl AovSample {
+    pub fn new() -> Self {
+        AovSample {
+            beauty: Color::new(),
+            diffuse_direct: Color::new(),
+            diffuse_indirect: Color::new(),
+            specular_direct: Color::new(),
+            specular_indirect: Color::new(),
+            emission: Color::new(),
+            albedo: Color::new(),
+            normal: Vec3::new(),
+            depth: None,
+            object_id: None,
+        }
+    }
+
+    /// Files radiance reaching the camera after `bounce` scattering events,
+    /// `specular` being the lobe picked at the first one
+    pub fn add_radiance(&mut self, radiance: Color<f64>, bounce: usize, specular: bool) {
+        self.beauty += &radiance;
+        let pass = match (bounce, specular) {
+            (0, _) => &mut self.emission,
+            (1, false) => &mut self.diffuse_direct,
+            (1, true) => &mut self.specular_direct,
+            (_, false) => &mut self.diffuse_indirect,
+            (_, true) => &mut self.specular_indirect,
+        };
+        *pass += &radiance;
+    }
+
+    /// Scales the radiance passes by the camera exposure
+    pub fn expose(&mut self, exposure: f64) {
+        self.beauty *= exposure;
+        self.diffuse_direct *= exposure;
+        self.diffuse_indirect *= exposure;
+        self.specular_direct *= exposure;
+        self.specular_indirect *= exposure;
+        self.emission *= exposure;
+    }
+}
+
+impl Default for AovSample {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Running sums of the samples taken inside a pixel
+#[derive(Clone, Copy)]
+pub struct AovPixel {
+    sum: AovSample,
+    depth_hits: usize,
+    pub samples: usize,
+    /// Welford running mean and squared deviations of the beauty luminance
+    luminance_mean: f64,
+    luminance_m2: f64,
+}
+
+impl AovPixel {
+    pub fn new() -> Self {
+        AovPixel {
+            sum: AovSample::new(),
+            depth_hits: 0,
+            samples: 0,
+            luminance_mean: 0.0,
+            luminance_m2: 0.0,
+        }
+    }
+
+    pub fn add(&mut self, sample: &AovSample) {
+        let sum = &mut self.sum;
+        sum.beauty += &sample.beauty;
+        sum.diffuse_direct += &sample.diffuse_direct;
+        sum.diffuse_indirect += &sample.diffuse_indirect;
+        sum.specular_direct += &sample.specular_direct;
+        sum.specular_indirect += &sample.specular_indirect;
+        sum.emission += &sample.emission;
+        sum.albedo += &sample.albedo;
+        sum.normal += &sample.normal;
+        if let Some(depth) = sample.depth {
+            sum.depth = Some(sum.depth.unwrap_or(0.0) + depth);
+            self.depth_hits += 1;
+        }
+        // Ids can't be averaged, the first sample decides
+        if self.samples == 0 {
+            sum.object_id = sample.object_id;
+        }
+        self.samples += 1;
+
+        let l = luminance(&sample.beauty);
+        let delta = l - self.luminance_mean;
+        self.luminance_mean += delta / self.samples as f64;
+        self.luminance_m2 += delta * (l - self.luminance_mean);
+    }
+
+    pub fn luminance_mean(&self) -> f64 {
+        self.luminance_mean
+    }
+
+    /// Unbiased sample variance of the beauty luminance
+    pub fn luminance_variance(&self) -> f64 {
+        match self.samples {
+            0 | 1 => 0.0,
+            n => self.luminance_m2 / (n - 1) as f64,
+        }
+    }
+
+    /// Mean value of a layer over the pixel samples.
+    /// Pixels that never hit anything have an infinite depth
+    /// and an object ID of 0, hit objects being numbered from 1
+    pub fn layer(&self, layer: AovLayer) -> Color<f64> {
+        let scale = 1.0 / usize::max(self.samples, 1) as f64;
+        let sum = &self.sum;
+        match layer {
+            AovLayer::Beauty => sum.beauty * scale,
+            AovLayer::DiffuseDirect => sum.diffuse_direct * scale,
+            AovLayer::DiffuseIndirect => sum.diffuse_indirect * scale,
+            AovLayer::SpecularDirect => sum.specular_direct * scale,
+            AovLayer::SpecularIndirect => sum.specular_indirect * scale,
+            AovLayer::Emission => sum.emission * scale,
+            AovLayer::Albedo => sum.albedo * scale,
+            AovLayer::Normal => match sum.normal.length() {
+                l if l > 0.0 => sum.normal / l,
+                _ => Vec3::new(),
+            },
+            AovLayer::Depth => {
+                let depth = match sum.depth {
+                    Some(d) => d / self.depth_hits as f64,
+                    None => f64::INFINITY,
+                };
+                Color::with_values(depth, depth, depth)
+            }
+            AovLayer::ObjectId => {
+                let id = sum.object_id.map_or(0.0, |id| (id + 1) as f64);
+                Color::with_values(id, id, id)
+            }
+        }
+    }
+}
+
+impl Default for AovPixel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The image being rendered, every pixel keeping all of its layers.
+/// The beauty pass is reconstructed by the film filter while the other
+/// ones are plain averages of the samples taken inside each pixel.
+pub struct FrameBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<AovPixel>,
+    pub film: Film,
+}
+
+impl FrameBuffer {
+    pub fn new(width: usize, height: usize, filter: Filter) -> Self {
+        FrameBuffer {
+            width,
+            height,
+            pixels: vec![AovPixel::new(); width * height],
+            film: Film::new(width, height, filter),
+        }
+    }
+
+    /// Adds a sample taken at the center of pixel (x, y). Convenient
+    /// when not rendering, threads should rather fill film tiles.
+    pub fn add_sample(&mut self, x: usize, y: usize, sample: &AovSample) {
+        self.pixels[y * self.width + x].add(sample);
+        let mut tile = self.film.tile(y, 1);
+        tile.add_sample((x as f64 + 0.5, y as f64 + 0.5), &sample.beauty);
+        self.film.merge(&tile);
+    }
+
+    /// Gamma corrected beauty pass ready to be encoded
+    pub fn to_u8(&self) -> Vec<Color<u8>> {
+        self.layer(AovLayer::Beauty)
+            .iter()
+            .map(|c| transform_to_u8_color(c, 1))
+            .collect()
+    }
+
+    pub fn layer(&self, layer: AovLayer) -> Vec<Color<f64>> {
+        match layer {
+            AovLayer::Beauty => self.film.resolve(1.0),
+            _ => self.pixels.iter().map(|p| p.layer(layer)).collect(),
+        }
+    }
+
+    /// Displayable version of a layer: radiance passes share the beauty
+    /// transform, normals are remapped to [0, 1], depth is shown
+    /// brighter when closer and object IDs get a random looking color
+    pub fn layer_preview(&self, layer: AovLayer) -> Vec<Color<u8>> {
+        let values = self.layer(layer);
+        match layer {
+            AovLayer::Normal => values
+                .iter()
+                .map(|n| {
+                    let mut c = *n;
+                    c += 1.0;
+                    to_u8_linear(&(c * 0.5))
+                })
+                .collect(),
+            AovLayer::Depth => {
+                let max = values
+                    .iter()
+                    .map(|d| d.x())
+                    .filter(|d| d.is_finite())
+                    .fold(0.0, f64::max);
+                values
+                    .iter()
+                    .map(|d| match d.x() {
+                        z if z.is_finite() && max > 0.0 => {
+                            let v = 1.0 - z / max;
+                            to_u8_linear(&Color::with_values(v, v, v))
+                        }
+                        _ => Color::new(),
+                    })
+                    .collect()
+            }
+            AovLayer::ObjectId => values.iter().map(|id| id_color(id.x() as u64)).collect(),
+            _ => values.iter().map(|c| transform_to_u8_color(c, 1)).collect(),
+        }
+    }
+
+    /// Writes every layer but the beauty one, which is
+    /// already written by the caller, next to `output`
+    pub fn write_aovs(&self, output: &str, format: AovFormat) -> Result<()> {
+        match format {
+            AovFormat::Exr => self.write_exr(&Path::new(output).with_extension("exr")),
+            AovFormat::Png => {
+                for layer in AovLayer::ALL.iter().skip(1) {
+                    let path = sibling_path(output, layer.name());
+                    crate::write_image_png(
+                        &path,
+                        &self.layer_preview(*layer),
+                        (self.width, self.height),
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Every layer goes into the same file, the beauty
+    /// one being the default RGB layer of the image
+    pub fn write_exr(&self, path: &Path) -> Result<()> {
+        let mut channels = Vec::new();
+        for layer in AovLayer::ALL.iter() {
+            let values = self.layer(*layer);
+            for (c, channel) in layer.channels().iter().enumerate() {
+                let name = match layer {
+                    AovLayer::Beauty => channel.to_string(),
+                    _ => format!("{}.{}", layer.name(), channel),
+                };
+                channels.push((name, values.iter().map(|v| v[c] as f32).collect()));
+            }
+        }
+        write_exr(path, self.width, self.height, channels)
+    }
+}
+
+/// `img.png` becomes `img.<layer>.png`, whatever the image format
+fn sibling_path(output: &str, layer: &str) -> String {
+    let path = Path::new(output);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("img");
+    path.with_file_name(format!("{}.{}.png", stem, layer))
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn to_u8_linear(c: &Color<f64>) -> Color<u8> {
+    Color::with_values(
+        (255.0 * clamp(c.x(), 0.0, 1.0)) as u8,
+        (255.0 * clamp(c.y(), 0.0, 1.0)) as u8,
+        (255.0 * clamp(c.z(), 0.0, 1.0)) as u8,
+    )
+}
+
+fn id_color(id: u64) -> Color<u8> {
+    if id == 0 {
+        return Color::new();
+    }
+    // Knuth multiplicative hash so that neighbouring ids look different
+    let h = id.wrapping_mul(2654435761) as u32;
+    Color::with_values(h as u8 | 0x20, (h >> 8) as u8 | 0x20, (h >> 16) as u8 | 0x20)
+}
@@ -1,60 +0,0 @@
-use crate::math::{degrees_to_radians, random_in_unit_disk, Point3, Ray, Vec3};
-
-pub struct Camera {
-    origin: Point3<f64>,
-    lower_left_corner: Point3<f64>,
-    horizontal: Vec3<f64>,
-    vertical: Vec3<f64>,
-    lens_radius: f64,
-    u: Vec3<f64>,
-    v: Vec3<f64>,
-    w: Vec3<f64>,
-}
-
-impl Camera {
-    pub fn new(
-        lookfrom: Point3<f64>,
-        lookat: Point3<f64>,
-        vup: Vec3<f64>,
-        vfov: f64,
-        aspect_ratio: f64,
-        aperture: f64,
-        focus_dist: f64,
-    ) -> Self {
-        let theta = degrees_to_radians(vfov);
-        let h = (theta / 2.0).tan();
-        let viewport_height = 2.0 * h;
-        let viewport_width = aspect_ratio * viewport_height;
-
-        let w = (lookfrom - lookat).unit_vec();
-        let u = (vup.cross(&w)).unit_vec();
-        let v = w.cross(&u);
-
-        let origin = lookfrom;
-        let horizontal = u * viewport_width * focus_dist;
-        let vertical = v * viewport_height * focus_dist;
-        let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - w * focus_dist;
-
-        let lens_radius = aperture / 2.;
-
-        Camera {
-            origin,
-            lower_left_corner,
-            horizontal,
-            vertical,
-            lens_radius,
-            u,
-            v,
-            w,
-        }
-    }
-
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = random_in_unit_disk() * self.lens_radius;
-        let offset = self.u * rd.x() + self.v * rd.y();
-        Ray::new(
-            self.origin + offset,
-            self.lower_left_corner + self.horizontal * s + self.vertical * t - self.origin - offset,
-        )
-    }
-}
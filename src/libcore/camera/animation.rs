@@ -0,0 +1,246 @@
+use crate::math::{Point3, Vec3};
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Placement and lens of a camera at a given frame
+#[derive(Clone, Copy, Debug)]
+pub struct CameraPose {
+    pub lookfrom: Point3<f64>,
+    pub lookat: Point3<f64>,
+    /// Vertical field of view in degrees
+    pub vfov: f64,
+    pub focus_dist: f64,
+}
+
+impl CameraPose {
+    fn lerp(&self, other: &CameraPose, t: f64) -> CameraPose {
+        CameraPose {
+            lookfrom: self.lookfrom + (other.lookfrom - self.lookfrom) * t,
+            lookat: self.lookat + (other.lookat - self.lookat) * t,
+            vfov: self.vfov + (other.vfov - self.vfov) * t,
+            focus_dist: self.focus_dist + (other.focus_dist - self.focus_dist) * t,
+        }
+    }
+
+    /// Catmull-Rom spline between `p1` and `p2`
+    fn catmull_rom(p0: &Self, p1: &Self, p2: &Self, p3: &Self, t: f64) -> CameraPose {
+        let vec = |a: Vec3<f64>, b: Vec3<f64>, c: Vec3<f64>, d: Vec3<f64>| {
+            (b * 2.0
+                + (c - a) * t
+                + (a * 2.0 - b * 5.0 + c * 4.0 - d) * (t * t)
+                + (b * 3.0 - a - c * 3.0 + d) * (t * t * t))
+                * 0.5
+        };
+        let scalar = |a: f64, b: f64, c: f64, d: f64| {
+            0.5 * (2.0 * b
+                + (c - a) * t
+                + (2.0 * a - 5.0 * b + 4.0 * c - d) * t * t
+                + (3.0 * b - a - 3.0 * c + d) * t * t * t)
+        };
+        CameraPose {
+            lookfrom: vec(p0.lookfrom, p1.lookfrom, p2.lookfrom, p3.lookfrom),
+            lookat: vec(p0.lookat, p1.lookat, p2.lookat, p3.lookat),
+            vfov: scalar(p0.vfov, p1.vfov, p2.vfov, p3.vfov),
+            focus_dist: scalar(p0.focus_dist, p1.focus_dist, p2.focus_dist, p3.focus_dist),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interpolation {
+    Linear,
+    /// Smooth spline going through every keyframe
+    CatmullRom,
+}
+
+/// Camera poses at some frames, interpolated in between
+/// and held before the first and after the last one
+#[derive(Clone, Debug)]
+pub struct CameraPath {
+    /// Sorted by frame
+    keyframes: Vec<(f64, CameraPose)>,
+    interpolation: Interpolation,
+}
+
+impl CameraPath {
+    pub fn new(mut keyframes: Vec<(f64, CameraPose)>, interpolation: Interpolation) -> Self {
+        assert!(!keyframes.is_empty(), "A camera path needs keyframes");
+        assert!(
+            keyframes.iter().all(|(frame, _)| frame.is_finite()),
+            "Keyframe times must be finite"
+        );
+        keyframes.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        CameraPath {
+            keyframes,
+            interpolation,
+        }
+    }
+
+    /// Reads keyframes written one per line as `frame lookfrom.x
+    /// lookfrom.y lookfrom.z lookat.x lookat.y lookat.z vfov focus_dist`,
+    /// `#` starting comments
+    pub fn from_file<P: AsRef<Path>>(path: P, interpolation: Interpolation) -> std::io::Result<Self> {
+        let invalid = |line: &str| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid camera keyframe: {}", line),
+            )
+        };
+
+        let mut keyframes = Vec::new();
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let v = line
+                .split_whitespace()
+                .map(|v| v.parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .map_err(|_| invalid(line))?;
+            // NaN and infinite frames could not be sorted
+            if v.len() != 9 || v.iter().any(|x| !x.is_finite()) {
+                return Err(invalid(line));
+            }
+            let pose = CameraPose {
+                lookfrom: Point3::with_values(v[1], v[2], v[3]),
+                lookat: Point3::with_values(v[4], v[5], v[6]),
+                vfov: v[7],
+                focus_dist: v[8],
+            };
+            keyframes.push((v[0], pose));
+        }
+        if keyframes.is_empty() {
+            return Err(invalid("no keyframe"));
+        }
+        Ok(CameraPath::new(keyframes, interpolation))
+    }
+
+    /// First and last keyframes
+    pub fn frames(&self) -> (f64, f64) {
+        (self.keyframes[0].0, self.keyframes[self.keyframes.len() - 1].0)
+    }
+
+    pub fn pose(&self, frame: f64) -> CameraPose {
+        let keys = &self.keyframes;
+        let next = match keys.iter().position(|(f, _)| *f > frame) {
+            Some(0) => return keys[0].1,
+            Some(i) => i,
+            None => return keys[keys.len() - 1].1,
+        };
+        let (f1, p1) = &keys[next - 1];
+        let (f2, p2) = &keys[next];
+        let t = (frame - f1) / (f2 - f1);
+        match self.interpolation {
+            Interpolation::Linear => p1.lerp(p2, t),
+            Interpolation::CatmullRom => {
+                // End tangents mirror the missing neighbours
+                let p0 = match next {
+                    1 => p1.lerp(p2, -1.0),
+                    _ => keys[next - 2].1,
+                };
+                let p3 = match keys.get(next + 1) {
+                    Some((_, p)) => *p,
+                    None => p1.lerp(p2, 2.0),
+                };
+                CameraPose::catmull_rom(&p0, p1, p2, &p3, t)
+            }
+        }
+    }
+}
+
+/// How the camera moves over the frames
+#[derive(Clone, Debug)]
+pub enum CameraAnimation {
+    Path(CameraPath),
+    /// Full orbit around the look-at point of `start` in `frames`
+    /// frames, around the vertical axis
+    Turntable { start: CameraPose, frames: usize },
+}
+
+impl CameraAnimation {
+    pub fn pose(&self, frame: f64) -> CameraPose {
+        match self {
+            CameraAnimation::Path(path) => path.pose(frame),
+            CameraAnimation::Turntable { start, frames } => {
+                let angle = 2.0 * PI * frame / *frames as f64;
+                let offset = start.lookfrom - start.lookat;
+                let (sin, cos) = angle.sin_cos();
+                let rotated = Vec3::with_values(
+                    offset.x() * cos + offset.z() * sin,
+                    offset.y(),
+                    -offset.x() * sin + offset.z() * cos,
+                );
+                CameraPose {
+                    lookfrom: start.lookat + rotated,
+                    ..*start
+                }
+            }
+        }
+    }
+
+    /// Frames covered by the animation, both ends included
+    pub fn frames(&self) -> (i64, i64) {
+        match self {
+            CameraAnimation::Path(path) => {
+                let (first, last) = path.frames();
+                (first.floor() as i64, last.ceil() as i64)
+            }
+            CameraAnimation::Turntable { frames, .. } => (0, *frames as i64 - 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CameraAnimation, CameraPath, CameraPose, Interpolation};
+    use crate::math::Point3;
+
+    fn pose(x: f64, vfov: f64) -> CameraPose {
+        CameraPose {
+            lookfrom: Point3::with_values(x, 1.0, 0.0),
+            lookat: Point3::new(),
+            vfov,
+            focus_dist: 10.0,
+        }
+    }
+
+    #[test]
+    fn paths_go_through_keyframes() {
+        let keys = vec![
+            (0.0, pose(0.0, 20.0)),
+            (10.0, pose(1.0, 40.0)),
+            (20.0, pose(4.0, 20.0)),
+            (30.0, pose(9.0, 30.0)),
+        ];
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom].iter() {
+            let path = CameraPath::new(keys.clone(), *interpolation);
+            for (frame, key) in &keys {
+                let p = path.pose(*frame);
+                assert!((p.lookfrom - key.lookfrom).length() < 1e-9);
+                assert!((p.vfov - key.vfov).abs() < 1e-9);
+            }
+            // Held outside of the keyframes
+            assert!((path.pose(-5.0).lookfrom - keys[0].1.lookfrom).length() < 1e-9);
+            assert!((path.pose(35.0).lookfrom - keys[3].1.lookfrom).length() < 1e-9);
+        }
+        let linear = CameraPath::new(keys.clone(), Interpolation::Linear);
+        assert!((linear.pose(5.0).lookfrom.x() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn turntable_orbits_at_constant_distance() {
+        let start = pose(5.0, 20.0);
+        let turntable = CameraAnimation::Turntable { start, frames: 8 };
+        assert_eq!(turntable.frames(), (0, 7));
+        for frame in 0..8 {
+            let p = turntable.pose(frame as f64);
+            assert!(((p.lookfrom - p.lookat).length() - 26f64.sqrt()).abs() < 1e-9);
+            assert!((p.lookfrom.y() - 1.0).abs() < 1e-9);
+        }
+        // Half way around
+        assert!((turntable.pose(4.0).lookfrom.x() + 5.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::math::{clamp, sample_unit_disk, Vec3};
+use std::f64::consts::PI;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Shape of the lens opening, which is also the shape
+/// of out of focus highlights
+#[derive(Clone)]
+pub enum ApertureShape {
+    Circular,
+    /// Regular polygon made by `blades` straight diaphragm blades,
+    /// `rotation` in radians
+    Polygon { blades: usize, rotation: f64 },
+    /// Arbitrary transmission image
+    Mask(Arc<ApertureMask>),
+}
+
+impl ApertureShape {
+    /// Point of the aperture for the uniform sample `u`, inside the
+    /// unit disk (unit square for masks), in lens units
+    pub fn sample(&self, u: (f64, f64)) -> Vec3<f64> {
+        match self {
+            ApertureShape::Circular => sample_unit_disk(u),
+            ApertureShape::Polygon { blades, rotation } => {
+                sample_polygon(usize::max(*blades, 3), *rotation, u)
+            }
+            ApertureShape::Mask(mask) => mask.sample(u),
+        }
+    }
+}
+
+/// Uniform point of a regular polygon inscribed in the unit circle,
+/// `u.0` choosing the triangle between the center and one side
+fn sample_polygon(sides: usize, rotation: f64, u: (f64, f64)) -> Vec3<f64> {
+    let scaled = u.0 * sides as f64;
+    let side = f64::min(scaled.floor(), (sides - 1) as f64);
+    let u0 = scaled - side;
+
+    let vertex = |k: f64| {
+        let angle = rotation + 2.0 * PI * k / sides as f64;
+        Vec3::with_values(angle.cos(), angle.sin(), 0.0)
+    };
+    let (a, b) = (vertex(side), vertex(side + 1.0));
+    // Uniform barycentric coordinates, the third vertex being the center
+    let su = u0.sqrt();
+    a * (su * (1.0 - u.1)) + b * (su * u.1)
+}
+
+/// Transmission image of the aperture, sampled in proportion to
+/// the brightness of its pixels. It covers the [-1, 1] square.
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    /// Cumulative distribution of the rows, then of the pixels
+    /// inside every row, top row first
+    row_cdf: Vec<f64>,
+    pixel_cdf: Vec<f64>,
+}
+
+impl ApertureMask {
+    /// `values` are the row-major transmissions, top row first
+    pub fn new(width: usize, height: usize, values: &[f64]) -> Self {
+        assert_eq!(values.len(), width * height, "Aperture mask size mismatch");
+        let mut pixel_cdf = Vec::with_capacity(width * height);
+        let mut row_cdf = Vec::with_capacity(height);
+        let mut total = 0.0;
+        for row in values.chunks(width) {
+            let mut sum = 0.0;
+            for v in row {
+                sum += f64::max(*v, 0.0);
+                pixel_cdf.push(sum);
+            }
+            total += sum;
+            row_cdf.push(total);
+        }
+        assert!(total > 0.0, "Aperture mask is fully opaque");
+        ApertureMask {
+            width,
+            height,
+            row_cdf,
+            pixel_cdf,
+        }
+    }
+
+    pub fn from_image<P: AsRef<Path>>(path: P) -> image::ImageResult<Self> {
+        let img = image::open(path)?.to_luma8();
+        let values: Vec<f64> = img.pixels().map(|p| p[0] as f64 / 255.0).collect();
+        Ok(ApertureMask::new(
+            img.width() as usize,
+            img.height() as usize,
+            &values,
+        ))
+    }
+
+    fn sample(&self, u: (f64, f64)) -> Vec3<f64> {
+        let (y, fy) = sample_cdf(&self.row_cdf, u.1);
+        let row = &self.pixel_cdf[y * self.width..(y + 1) * self.width];
+        let (x, fx) = sample_cdf(row, u.0);
+        Vec3::with_values(
+            (x as f64 + fx) / self.width as f64 * 2.0 - 1.0,
+            1.0 - (y as f64 + fy) / self.height as f64 * 2.0,
+            0.0,
+        )
+    }
+}
+
+/// Index picked by `u` in the unnormalized cumulative distribution
+/// `cdf`, with the position of `u` inside that bucket
+fn sample_cdf(cdf: &[f64], u: f64) -> (usize, f64) {
+    let total = cdf[cdf.len() - 1];
+    let target = u * total;
+    let index = cdf.iter().position(|c| *c > target).unwrap_or(cdf.len() - 1);
+    let start = if index == 0 { 0.0 } else { cdf[index - 1] };
+    let width = cdf[index] - start;
+    let offset = if width > 0.0 {
+        (target - start) / width
+    } else {
+        0.5
+    };
+    (index, clamp(offset, 0.0, 1.0 - 1e-9))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApertureMask, ApertureShape};
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    #[test]
+    fn polygon_samples_stay_inside() {
+        let blades = 6;
+        let rotation = 0.3;
+        let shape = ApertureShape::Polygon { blades, rotation };
+        // Apothem, the distance from the center to the sides
+        let apothem = (PI / blades as f64).cos();
+        for i in 0..32 {
+            for j in 0..32 {
+                let p = shape.sample(((i as f64 + 0.5) / 32.0, (j as f64 + 0.5) / 32.0));
+                for k in 0..blades {
+                    let normal_angle = rotation + (2.0 * k as f64 + 1.0) * PI / blades as f64;
+                    let d = p.x() * normal_angle.cos() + p.y() * normal_angle.sin();
+                    assert!(d <= apothem + 1e-9, "{:?}", p);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mask_samples_avoid_opaque_pixels() {
+        // Only the top right pixel is open
+        let mask = ApertureMask::new(2, 2, &[0.0, 1.0, 0.0, 0.0]);
+        let shape = ApertureShape::Mask(Arc::new(mask));
+        for i in 0..16 {
+            for j in 0..16 {
+                let p = shape.sample((i as f64 / 16.0, j as f64 / 16.0));
+                assert!(p.x() >= 0.0 && p.x() <= 1.0, "{:?}", p);
+                assert!(p.y() >= 0.0 && p.y() <= 1.0, "{:?}", p);
+            }
+        }
+    }
+}
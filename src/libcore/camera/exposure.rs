@@ -0,0 +1,69 @@
+use super::Shutter;
+
+/// Height of a full frame (35mm) sensor in millimeters
+const SENSOR_HEIGHT: f64 = 24.0;
+
+/// Camera settings of a real camera. They fix the exposure of the image
+/// as well as the depth of field and the motion blur. Scene units are
+/// meters and seconds, radiance is in cd/m².
+#[derive(Clone, Copy, Debug)]
+pub struct PhysicalCamera {
+    pub f_number: f64,
+    /// Exposure time in seconds
+    pub shutter_speed: f64,
+    pub iso: f64,
+}
+
+impl PhysicalCamera {
+    pub fn new(f_number: f64, shutter_speed: f64, iso: f64) -> Self {
+        PhysicalCamera {
+            f_number,
+            shutter_speed,
+            iso,
+        }
+    }
+
+    /// Exposure value at ISO 100 of these settings
+    pub fn ev100(&self) -> f64 {
+        (self.f_number * self.f_number / self.shutter_speed).log2() - (self.iso / 100.0).log2()
+    }
+
+    /// Multiplier turning luminance into pixel values, 1 being the
+    /// saturation of the sensor (saturation based sensitivity)
+    pub fn exposure(&self) -> f64 {
+        1.0 / (1.2 * 2f64.powf(self.ev100()))
+    }
+
+    /// Focal length in millimeters of a full frame camera
+    /// with a vertical field of view of `vfov` radians
+    pub fn focal_length(&self, vfov: f64) -> f64 {
+        SENSOR_HEIGHT / 2.0 / (vfov / 2.0).tan()
+    }
+
+    /// Diameter of the entrance pupil in meters
+    pub fn aperture_diameter(&self, vfov: f64) -> f64 {
+        self.focal_length(vfov) / self.f_number / 1000.0
+    }
+
+    pub fn shutter(&self) -> Shutter {
+        Shutter::new(0.0, self.shutter_speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PhysicalCamera;
+
+    #[test]
+    fn exposure_value() {
+        assert!(PhysicalCamera::new(1.0, 1.0, 100.0).ev100().abs() < 1e-9);
+        // Doubling the sensitivity is a stop down
+        assert!((PhysicalCamera::new(1.0, 1.0, 200.0).ev100() + 1.0).abs() < 1e-9);
+        // Sunny 16 rule
+        let sunny = PhysicalCamera::new(16.0, 1.0 / 125.0, 100.0);
+        assert!((sunny.ev100() - 15.0).abs() < 0.05);
+        // Four times the light two stops wider
+        let ratio = PhysicalCamera::new(8.0, 1.0 / 125.0, 100.0).exposure() / sunny.exposure();
+        assert!((ratio - 4.0).abs() < 1e-9);
+    }
+}
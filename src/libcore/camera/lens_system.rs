@@ -0,0 +1,307 @@
+use super::Camera;
+use crate::libcore::sampler::Sampler;
+use crate::math::{refract, sample_unit_disk, schlick, Point3, Ray, Vec3};
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Scene units per millimeter, lens prescriptions being in millimeters
+const MILLIMETER: f64 = 0.001;
+
+/// Height of the film in millimeters, full frame
+const FILM_HEIGHT: f64 = 24.0;
+
+/// One interface of a lens prescription, listed from the front
+/// (object side) to the back (film side), lengths in millimeters
+#[derive(Clone, Copy, Debug)]
+pub struct LensElement {
+    /// Signed radius of the spherical surface, positive when its
+    /// center is behind it. Zero for the aperture stop.
+    pub curvature_radius: f64,
+    /// Distance to the next interface along the optical axis
+    pub thickness: f64,
+    /// Index of refraction of the medium behind the interface
+    pub ior: f64,
+    pub aperture_radius: f64,
+}
+
+impl LensElement {
+    fn is_stop(&self) -> bool {
+        self.curvature_radius == 0.0
+    }
+}
+
+/// Reads a lens prescription: one interface per line with its curvature
+/// radius, thickness, index of refraction and aperture diameter, `#`
+/// starting comments. An index of 0 stands for air. The thickness of the
+/// last interface is replaced by the film distance when focusing.
+pub fn read_prescription<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<LensElement>> {
+    let invalid = |line: &str| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid lens prescription line: {}", line),
+        )
+    };
+
+    let mut elements = Vec::new();
+    for line in fs::read_to_string(path)?.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let values = line
+            .split_whitespace()
+            .map(|v| v.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| invalid(line))?;
+        if values.len() != 4 {
+            return Err(invalid(line));
+        }
+        elements.push(LensElement {
+            curvature_radius: values[0],
+            thickness: values[1],
+            ior: if values[2] == 0.0 { 1.0 } else { values[2] },
+            aperture_radius: values[3] / 2.0,
+        });
+    }
+    if elements.is_empty() {
+        return Err(invalid("no lens element"));
+    }
+    Ok(elements)
+}
+
+/// Camera tracing rays through every surface of a real lens design,
+/// giving its distortion, vignetting and focus breathing. Rays blocked
+/// by an aperture or lost to reflections at an interface are dropped.
+pub struct LensSystemCamera {
+    elements: Vec<LensElement>,
+    /// Film size in millimeters
+    film_width: f64,
+    film_height: f64,
+    origin: Point3<f64>,
+    u: Vec3<f64>,
+    v: Vec3<f64>,
+    w: Vec3<f64>,
+}
+
+impl LensSystemCamera {
+    /// The film sits at `lookfrom`, the lens being focused `focus_dist`
+    /// scene units in front of it
+    pub fn new(
+        lookfrom: Point3<f64>,
+        lookat: Point3<f64>,
+        vup: Vec3<f64>,
+        elements: Vec<LensElement>,
+        aspect_ratio: f64,
+        focus_dist: f64,
+    ) -> Self {
+        let w = (lookfrom - lookat).unit_vec();
+        let u = (vup.cross(&w)).unit_vec();
+        let v = w.cross(&u);
+
+        let mut camera = LensSystemCamera {
+            elements,
+            film_width: FILM_HEIGHT * aspect_ratio,
+            film_height: FILM_HEIGHT,
+            origin: lookfrom,
+            u,
+            v,
+            w,
+        };
+        camera.focus(focus_dist / MILLIMETER);
+        camera
+    }
+
+    fn rear(&self) -> &LensElement {
+        &self.elements[self.elements.len() - 1]
+    }
+
+    /// Moves the film so that objects `distance` millimeters away
+    /// from it are sharp
+    fn focus(&mut self, distance: f64) {
+        let target = 1.0 / distance;
+        // The object distance only gets shorter with the film distance
+        // up to about twice the focal length (1:1 magnification),
+        // the first crossing of the target is the right one
+        let (mut low, mut high) = (0.0, 1.0);
+        while high < 1e5 {
+            match self.vergence(high) {
+                v if v >= target => break,
+                _ => {
+                    low = high;
+                    high *= 2.0;
+                }
+            }
+        }
+        for _ in 0..64 {
+            let mid = (low + high) / 2.0;
+            match self.vergence(mid) {
+                v if v >= target => high = mid,
+                _ => low = mid,
+            }
+        }
+        let last = self.elements.len() - 1;
+        self.elements[last].thickness = high;
+    }
+
+    /// Inverse of the distance from the film to the point imaged on the
+    /// center of the film when it is `film_distance` behind the lens,
+    /// found where a paraxial ray from there meets the axis again.
+    /// Negative infinity when no real object is in focus.
+    fn vergence(&mut self, film_distance: f64) -> f64 {
+        let last = self.elements.len() - 1;
+        self.elements[last].thickness = film_distance;
+        let height = 0.01 * self.rear().aperture_radius;
+        let ray = Ray::new(Point3::new(), Vec3::with_values(height, 0.0, -film_distance));
+        match self.trace_from_film(&ray, false) {
+            Some((out, _)) => match -out.origin.x() / out.direction.x() {
+                t if t > 0.0 => -1.0 / out.at(t).z(),
+                // Diverging rays, the object is a virtual one
+                _ => f64::NEG_INFINITY,
+            },
+            None => f64::NAN,
+        }
+    }
+
+    /// Traces a ray in lens space, from the film at z = 0 towards the
+    /// scene along -z. Returns the ray leaving the front element and the
+    /// Fresnel transmittance through all the interfaces.
+    fn trace_from_film(&self, ray: &Ray, check_apertures: bool) -> Option<(Ray, f64)> {
+        let mut ray = Ray::new(ray.origin, ray.direction.unit_vec());
+        let mut transmittance = 1.0;
+        let mut z = 0.0;
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            z -= element.thickness;
+            let (t, normal) = match element.is_stop() {
+                true => ((z - ray.origin.z()) / ray.direction.z(), None),
+                false => {
+                    let center = z + element.curvature_radius;
+                    let (t, normal) = intersect_surface(element.curvature_radius, center, &ray)?;
+                    (t, Some(normal))
+                }
+            };
+            let p = ray.at(t);
+            if check_apertures
+                && p.x() * p.x() + p.y() * p.y() > element.aperture_radius * element.aperture_radius
+            {
+                return None;
+            }
+            ray.origin = p;
+
+            if let Some(normal) = normal {
+                let eta_i = element.ior;
+                let eta_t = match i {
+                    0 => 1.0,
+                    _ => self.elements[i - 1].ior,
+                };
+                let ratio = eta_i / eta_t;
+                let cos_theta = f64::min(normal.dot(-ray.direction), 1.0);
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                if ratio * sin_theta > 1.0 {
+                    // Total internal reflection
+                    return None;
+                }
+                transmittance *= 1.0 - schlick(cos_theta, ratio);
+                ray.direction = refract(&ray.direction, &normal, ratio).unit_vec();
+            }
+        }
+        Some((ray, transmittance))
+    }
+}
+
+/// Hit of a spherical interface centered on the axis at `z_center`,
+/// with the normal facing the ray
+fn intersect_surface(radius: f64, z_center: f64, ray: &Ray) -> Option<(f64, Vec3<f64>)> {
+    let o = ray.origin - Vec3::with_values(0.0, 0.0, z_center);
+    let a = ray.direction.length_squared();
+    let half_b = o.dot(ray.direction);
+    let c = o.length_squared() - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let (t0, t1) = ((-half_b - root) / a, (-half_b + root) / a);
+    // The surface is the half of the sphere facing the film for
+    // positive radii going towards the scene
+    let closer = (ray.direction.z() > 0.0) ^ (radius < 0.0);
+    let t = if closer { t0 } else { t1 };
+    if t < 0.0 {
+        return None;
+    }
+    let mut normal = (o + ray.direction * t).unit_vec();
+    if normal.dot(ray.direction) > 0.0 {
+        normal = -normal;
+    }
+    Some((t, normal))
+}
+
+impl Camera for LensSystemCamera {
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        // The lens flips the image
+        let film = Point3::with_values(
+            (0.5 - s) * self.film_width,
+            (0.5 - t) * self.film_height,
+            0.0,
+        );
+        let rear = self.rear();
+        let lens = sample_unit_disk(sampler.get_2d()) * rear.aperture_radius;
+        let target = Point3::with_values(lens.x(), lens.y(), -rear.thickness);
+
+        let (out, transmittance) = self.trace_from_film(&Ray::new(film, target - film), true)?;
+        if sampler.get_1d() > transmittance {
+            return None;
+        }
+
+        let to_world = |p: Vec3<f64>| self.u * p.x() + self.v * p.y() + self.w * p.z();
+        Some(Ray::new(
+            self.origin + to_world(out.origin) * MILLIMETER,
+            to_world(out.direction),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LensElement, LensSystemCamera};
+    use crate::math::{Point3, Ray, Vec3};
+
+    /// Biconvex lens of about 100mm focal length
+    fn singlet() -> Vec<LensElement> {
+        // Thin lens maker's equation: 1 / f = (n - 1) * (1 / R1 - 1 / R2)
+        vec![
+            LensElement {
+                curvature_radius: 100.0,
+                thickness: 2.0,
+                ior: 1.5,
+                aperture_radius: 10.0,
+            },
+            LensElement {
+                curvature_radius: -100.0,
+                thickness: 0.0,
+                ior: 1.0,
+                aperture_radius: 10.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn focuses_at_infinity_near_the_focal_length() {
+        let camera = LensSystemCamera::new(
+            Point3::new(),
+            Point3::with_values(0.0, 0.0, -1.0),
+            Vec3::with_values(0.0, 1.0, 0.0),
+            singlet(),
+            1.5,
+            1e6,
+        );
+        let back = camera.rear().thickness;
+        assert!((back - 100.0).abs() < 2.0, "{}", back);
+
+        // A ray from the film center comes out parallel to the axis
+        let ray = Ray::new(Point3::new(), Vec3::with_values(0.5, 0.0, -back));
+        let (out, transmittance) = camera.trace_from_film(&ray, true).unwrap();
+        assert!(out.direction.x().abs() < 1e-3, "{:?}", out.direction);
+        assert!(transmittance > 0.9 && transmittance < 1.0);
+    }
+}
@@ -0,0 +1,31 @@
+use crate::libcore::sampler::Sampler;
+use crate::math::Ray;
+
+mod animation;
+mod aperture;
+mod exposure;
+mod lens_system;
+mod orthographic;
+mod panoramic;
+mod perspective;
+mod shutter;
+mod stereo;
+
+pub use animation::{CameraAnimation, CameraPath, CameraPose, Interpolation};
+pub use aperture::{ApertureMask, ApertureShape};
+pub use exposure::PhysicalCamera;
+pub use lens_system::{read_prescription, LensElement, LensSystemCamera};
+pub use orthographic::OrthographicCamera;
+pub use panoramic::{CubemapCamera, EquirectangularCamera, FisheyeCamera, FisheyeMapping};
+pub use perspective::PerspectiveCamera;
+pub use shutter::{Shutter, ShutterCamera};
+pub use stereo::{compose_anaglyph, OdsCamera, StereoCamera, StereoLayout};
+
+pub trait Camera {
+    /// Ray leaving the camera through the image point (s, t),
+    /// both in [0, 1] with (0, 0) being the lower left corner.
+    /// None when the projection doesn't cover that point,
+    /// the sample is then black. Rays are traced at time 0
+    /// unless the camera is wrapped in a `ShutterCamera`.
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Option<Ray>;
+}
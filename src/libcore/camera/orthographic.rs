@@ -0,0 +1,82 @@
+use super::Camera;
+use crate::libcore::sampler::Sampler;
+use crate::math::{Point3, Ray, Vec3};
+
+/// Parallel projection, every ray going in the viewing direction
+/// from a point of the view rectangle. Sizes don't change with the
+/// distance which suits technical and architectural views.
+pub struct OrthographicCamera {
+    lower_left_corner: Point3<f64>,
+    horizontal: Vec3<f64>,
+    vertical: Vec3<f64>,
+    direction: Vec3<f64>,
+}
+
+impl OrthographicCamera {
+    /// `view_width` is the width of the visible area in world units
+    pub fn new(
+        lookfrom: Point3<f64>,
+        lookat: Point3<f64>,
+        vup: Vec3<f64>,
+        view_width: f64,
+        aspect_ratio: f64,
+    ) -> Self {
+        let w = (lookfrom - lookat).unit_vec();
+        let u = (vup.cross(&w)).unit_vec();
+        let v = w.cross(&u);
+
+        let horizontal = u * view_width;
+        let vertical = v * (view_width / aspect_ratio);
+        let lower_left_corner = lookfrom - horizontal / 2.0 - vertical / 2.0;
+
+        OrthographicCamera {
+            lower_left_corner,
+            horizontal,
+            vertical,
+            direction: -w,
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, s: f64, t: f64, _: &mut dyn Sampler) -> Option<Ray> {
+        Some(Ray::new(
+            self.lower_left_corner + self.horizontal * s + self.vertical * t,
+            self.direction,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OrthographicCamera;
+    use crate::libcore::camera::Camera;
+    use crate::libcore::sampler::IndependentSampler;
+    use crate::math::{Point3, Vec3};
+
+    #[test]
+    fn rays_are_parallel_across_the_view() {
+        let camera = OrthographicCamera::new(
+            Point3::with_values(0.0, 0.0, 5.0),
+            Point3::new(),
+            Vec3::with_values(0.0, 1.0, 0.0),
+            8.0,
+            2.0,
+        );
+        let mut sampler = IndependentSampler::new(0);
+        let mut ray = |s, t| camera.get_ray(s, t, &mut sampler).unwrap();
+
+        let forward = Vec3::with_values(0.0, 0.0, -1.0);
+        for &(s, t) in [(0.0, 0.0), (0.5, 0.5), (1.0, 0.25), (0.3, 1.0)].iter() {
+            assert!((ray(s, t).direction.unit_vec() - forward).length() < 1e-12);
+        }
+        let expected = [
+            ((0.0, 0.0), Point3::with_values(-4.0, -2.0, 5.0)),
+            ((0.5, 0.5), Point3::with_values(0.0, 0.0, 5.0)),
+            ((1.0, 1.0), Point3::with_values(4.0, 2.0, 5.0)),
+        ];
+        for &((s, t), origin) in expected.iter() {
+            assert!((ray(s, t).origin - origin).length() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use super::Camera;
+use crate::libcore::sampler::Sampler;
+use crate::math::{degrees_to_radians, Point3, Ray, Vec3};
+use std::f64::consts::PI;
+
+/// Orthonormal frame shared by the cameras looking all around them,
+/// `forward` being the center of the image
+#[derive(Clone, Copy)]
+pub(super) struct ViewFrame {
+    pub origin: Point3<f64>,
+    pub right: Vec3<f64>,
+    pub up: Vec3<f64>,
+    pub forward: Vec3<f64>,
+}
+
+impl ViewFrame {
+    pub fn new(lookfrom: Point3<f64>, lookat: Point3<f64>, vup: Vec3<f64>) -> Self {
+        let w = (lookfrom - lookat).unit_vec();
+        let u = (vup.cross(&w)).unit_vec();
+        let v = w.cross(&u);
+        ViewFrame {
+            origin: lookfrom,
+            right: u,
+            up: v,
+            forward: -w,
+        }
+    }
+
+    /// Direction at longitude `phi` (0 being forward, positive to the
+    /// right) and latitude `theta` (positive up)
+    pub fn spherical(&self, phi: f64, theta: f64) -> Vec3<f64> {
+        self.forward * (theta.cos() * phi.cos())
+            + self.right * (theta.cos() * phi.sin())
+            + self.up * theta.sin()
+    }
+}
+
+/// Latitude-longitude projection covering the full sphere around the
+/// camera, the image should have a 2:1 aspect ratio
+pub struct EquirectangularCamera {
+    frame: ViewFrame,
+}
+
+impl EquirectangularCamera {
+    pub fn new(lookfrom: Point3<f64>, lookat: Point3<f64>, vup: Vec3<f64>) -> Self {
+        EquirectangularCamera {
+            frame: ViewFrame::new(lookfrom, lookat, vup),
+        }
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, s: f64, t: f64, _: &mut dyn Sampler) -> Option<Ray> {
+        let (phi, theta) = equirectangular_angles(s, t);
+        Some(Ray::new(self.frame.origin, self.frame.spherical(phi, theta)))
+    }
+}
+
+/// Longitude and latitude of the image point (s, t) of a
+/// latitude-longitude image
+pub(super) fn equirectangular_angles(s: f64, t: f64) -> (f64, f64) {
+    ((s - 0.5) * 2.0 * PI, (t - 0.5) * PI)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FisheyeMapping {
+    /// Distance to the image center proportional to the angle
+    Equidistant,
+    /// Preserves areas, distance proportional to sin(angle / 2)
+    Equisolid,
+}
+
+/// Circular fisheye, the image circle touching the top and
+/// bottom of the image. Pixels outside of it stay black.
+pub struct FisheyeCamera {
+    frame: ViewFrame,
+    half_fov: f64,
+    aspect_ratio: f64,
+    mapping: FisheyeMapping,
+}
+
+impl FisheyeCamera {
+    /// `fov` is the angle covered by the image circle diameter,
+    /// in degrees, up to 360 for the equidistant mapping
+    pub fn new(
+        lookfrom: Point3<f64>,
+        lookat: Point3<f64>,
+        vup: Vec3<f64>,
+        fov: f64,
+        aspect_ratio: f64,
+        mapping: FisheyeMapping,
+    ) -> Self {
+        let max_fov = match mapping {
+            FisheyeMapping::Equidistant => 360.0,
+            FisheyeMapping::Equisolid => 359.0,
+        };
+        FisheyeCamera {
+            frame: ViewFrame::new(lookfrom, lookat, vup),
+            half_fov: degrees_to_radians(f64::min(fov, max_fov)) / 2.0,
+            aspect_ratio,
+            mapping,
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, s: f64, t: f64, _: &mut dyn Sampler) -> Option<Ray> {
+        let x = (2.0 * s - 1.0) * self.aspect_ratio;
+        let y = 2.0 * t - 1.0;
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+        // Angle between the ray and the optical axis
+        let angle = match self.mapping {
+            FisheyeMapping::Equidistant => r * self.half_fov,
+            FisheyeMapping::Equisolid => 2.0 * (r * (self.half_fov / 2.0).sin()).asin(),
+        };
+        let radial = match r {
+            r if r > 0.0 => self.frame.right * (x / r) + self.frame.up * (y / r),
+            _ => Vec3::new(),
+        };
+        let direction = self.frame.forward * angle.cos() + radial * angle.sin();
+        Some(Ray::new(self.frame.origin, direction))
+    }
+}
+
+/// Six 90° faces laid out in a 3x2 grid: left, front, right on the top
+/// row then back, up, down on the bottom one. Side faces keep the
+/// camera up vector, the up and down faces have the front face below
+/// and above them respectively. The image must be 3 faces wide and 2
+/// high so that every pixel falls in a single face.
+pub struct CubemapCamera {
+    frame: ViewFrame,
+    /// Width and height of a face in pixels
+    face_size: usize,
+}
+
+impl CubemapCamera {
+    pub fn new(
+        lookfrom: Point3<f64>,
+        lookat: Point3<f64>,
+        vup: Vec3<f64>,
+        face_size: usize,
+    ) -> Self {
+        CubemapCamera {
+            frame: ViewFrame::new(lookfrom, lookat, vup),
+            face_size,
+        }
+    }
+
+    /// (forward, right, up) axes of a face
+    fn face(&self, index: usize) -> (Vec3<f64>, Vec3<f64>, Vec3<f64>) {
+        let f = &self.frame;
+        match index {
+            0 => (-f.right, f.forward, f.up),
+            1 => (f.forward, f.right, f.up),
+            2 => (f.right, -f.forward, f.up),
+            3 => (-f.forward, -f.right, f.up),
+            4 => (f.up, f.right, -f.forward),
+            _ => (-f.up, f.right, f.forward),
+        }
+    }
+}
+
+impl Camera for CubemapCamera {
+    fn get_ray(&self, s: f64, t: f64, _: &mut dyn Sampler) -> Option<Ray> {
+        // Back to pixel coordinates, s and t running over width - 1
+        // and height - 1 pixels
+        let size = self.face_size as f64;
+        let x = s * (3 * self.face_size - 1) as f64;
+        let y = t * (2 * self.face_size - 1) as f64;
+        let column = usize::min(x as usize / self.face_size, 2);
+        let row = if y as usize >= self.face_size { 0 } else { 1 };
+        // Position inside the face in [-1, 1]
+        let a = (x - (column * self.face_size) as f64) / size * 2.0 - 1.0;
+        let b = (y - ((1 - row) * self.face_size) as f64) / size * 2.0 - 1.0;
+
+        let (forward, right, up) = self.face(row * 3 + column);
+        Some(Ray::new(self.frame.origin, forward + right * a + up * b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CubemapCamera, EquirectangularCamera, FisheyeCamera, FisheyeMapping};
+    use crate::libcore::camera::Camera;
+    use crate::libcore::sampler::IndependentSampler;
+    use crate::math::{Point3, Vec3};
+
+    /// Looking down -z from the origin, x to the right and y up
+    fn view() -> (Point3<f64>, Point3<f64>, Vec3<f64>) {
+        (
+            Point3::new(),
+            Point3::with_values(0.0, 0.0, -1.0),
+            Vec3::with_values(0.0, 1.0, 0.0),
+        )
+    }
+
+    fn direction(camera: &dyn Camera, s: f64, t: f64) -> Option<Vec3<f64>> {
+        let mut sampler = IndependentSampler::new(0);
+        camera
+            .get_ray(s, t, &mut sampler)
+            .map(|ray| ray.direction.unit_vec())
+    }
+
+    fn assert_close(a: Option<Vec3<f64>>, b: Vec3<f64>) {
+        let a = a.unwrap();
+        assert!((a - b).length() < 1e-9, "{:?} {:?}", a, b);
+    }
+
+    #[test]
+    fn equirectangular_covers_the_sphere() {
+        let (from, at, up) = view();
+        let camera = EquirectangularCamera::new(from, at, up);
+        let d = |s, t| direction(&camera, s, t);
+        assert_close(d(0.5, 0.5), Vec3::with_values(0.0, 0.0, -1.0));
+        assert_close(d(0.75, 0.5), Vec3::with_values(1.0, 0.0, 0.0));
+        assert_close(d(0.25, 0.5), Vec3::with_values(-1.0, 0.0, 0.0));
+        assert_close(d(0.0, 0.5), Vec3::with_values(0.0, 0.0, 1.0));
+        assert_close(d(0.3, 1.0), Vec3::with_values(0.0, 1.0, 0.0));
+        assert_close(d(0.3, 0.0), Vec3::with_values(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn fisheye_angle_follows_the_mapping() {
+        let (from, at, up) = view();
+        let equidistant = FisheyeCamera::new(from, at, up, 180.0, 1.0, FisheyeMapping::Equidistant);
+        let d = |s, t| direction(&equidistant, s, t);
+        assert_close(d(0.5, 0.5), Vec3::with_values(0.0, 0.0, -1.0));
+        // The edge of the circle is 90° off axis, halfway 45°
+        assert_close(d(1.0, 0.5), Vec3::with_values(1.0, 0.0, 0.0));
+        assert_close(d(0.5, 0.75), Vec3::with_values(0.0, 1.0f64, -1.0).unit_vec());
+        assert!(d(1.0, 1.0).is_none());
+
+        // Halfway to the edge the equisolid angle is 2 asin(sin(45°) / 2)
+        let equisolid = FisheyeCamera::new(from, at, up, 180.0, 1.0, FisheyeMapping::Equisolid);
+        let angle = 2.0 * (std::f64::consts::FRAC_1_SQRT_2 / 2.0).asin();
+        assert_close(
+            direction(&equisolid, 0.75, 0.5),
+            Vec3::with_values(angle.sin(), 0.0, -angle.cos()),
+        );
+        assert_close(
+            direction(&equisolid, 1.0, 0.5),
+            Vec3::with_values(1.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn cubemap_faces_split_on_pixels() {
+        let (from, at, up) = view();
+        let size = 4;
+        let camera = CubemapCamera::new(from, at, up, size);
+        // Image point of the pixel (i, j) from the top left corner,
+        // as sampled by the renderer
+        let d = |i: f64, j: f64| {
+            let s = i / (3 * size - 1) as f64;
+            let t = (2 * size) as f64 - j;
+            direction(&camera, s, t / (2 * size - 1) as f64)
+        };
+
+        let faces = [
+            Vec3::with_values(-1.0, 0.0, 0.0),
+            Vec3::with_values(0.0, 0.0, -1.0),
+            Vec3::with_values(1.0, 0.0, 0.0),
+            Vec3::with_values(0.0, 0.0, 1.0),
+            Vec3::with_values(0.0, 1.0, 0.0),
+            Vec3::with_values(0.0, -1.0, 0.0),
+        ];
+        for (index, forward) in faces.iter().enumerate() {
+            let (column, row) = ((index % 3) as f64, (index / 3) as f64);
+            let center = d((column + 0.5) * size as f64, (row + 0.5) * size as f64);
+            assert_close(center, *forward);
+        }
+
+        // Both sides of the left | front border see along the cube edge
+        let edge = Vec3::with_values(-1.0, 0.0, -1.0f64).unit_vec();
+        let half = size as f64 / 2.0;
+        assert_close(d(size as f64 - 1e-9, half), edge);
+        assert_close(d(size as f64, half), edge);
+        // Samples next to the border stay in their own face
+        let left = d(size as f64 - 0.01, half).unwrap();
+        assert!(left.x().abs() > left.z().abs());
+        let front = d(size as f64 + 0.01, half).unwrap();
+        assert!(front.z().abs() > front.x().abs());
+    }
+}
@@ -0,0 +1,214 @@
+use super::{ApertureShape, Camera};
+use crate::libcore::sampler::Sampler;
+use crate::math::{degrees_to_radians, Point3, Ray, Vec3};
+
+/// Thin lens perspective camera
+#[derive(Clone)]
+pub struct PerspectiveCamera {
+    origin: Point3<f64>,
+    lower_left_corner: Point3<f64>,
+    horizontal: Vec3<f64>,
+    vertical: Vec3<f64>,
+    lens_radius: f64,
+    focus_dist: f64,
+    aperture_shape: ApertureShape,
+    /// Shift of the lens barrel opening, relative to the aperture
+    /// radius, seen from the image corners
+    cat_eye: f64,
+    /// Natural cos^4 falloff towards the image borders
+    vignetting: bool,
+    /// Offset of the image window from the optical axis,
+    /// in fractions of the image width and height
+    shift: (f64, f64),
+    /// Point and normal of a tilted plane of focus
+    focus_plane: Option<(Point3<f64>, Vec3<f64>)>,
+    u: Vec3<f64>,
+    v: Vec3<f64>,
+    w: Vec3<f64>,
+}
+
+impl PerspectiveCamera {
+    pub fn new(
+        lookfrom: Point3<f64>,
+        lookat: Point3<f64>,
+        vup: Vec3<f64>,
+        vfov: f64,
+        aspect_ratio: f64,
+        aperture: f64,
+        focus_dist: f64,
+    ) -> Self {
+        let theta = degrees_to_radians(vfov);
+        let h = (theta / 2.0).tan();
+        let viewport_height = 2.0 * h;
+        let viewport_width = aspect_ratio * viewport_height;
+
+        let w = (lookfrom - lookat).unit_vec();
+        let u = (vup.cross(&w)).unit_vec();
+        let v = w.cross(&u);
+
+        let origin = lookfrom;
+        let horizontal = u * viewport_width * focus_dist;
+        let vertical = v * viewport_height * focus_dist;
+        let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - w * focus_dist;
+
+        let lens_radius = aperture / 2.;
+
+        PerspectiveCamera {
+            origin,
+            lower_left_corner,
+            horizontal,
+            vertical,
+            lens_radius,
+            focus_dist,
+            aperture_shape: ApertureShape::Circular,
+            cat_eye: 0.0,
+            vignetting: false,
+            shift: (0.0, 0.0),
+            focus_plane: None,
+            u,
+            v,
+            w,
+        }
+    }
+
+    /// Same camera moved by `offset` along its horizontal axis, the view
+    /// window being shifted so that it still matches the original one
+    /// `convergence` away from the camera
+    pub fn with_eye_offset(&self, offset: f64, convergence: f64) -> Self {
+        let shift = self.u * offset;
+        let window_shift = self.u * (offset * self.focus_dist / convergence);
+        PerspectiveCamera {
+            origin: self.origin + shift,
+            lower_left_corner: self.lower_left_corner + shift - window_shift,
+            ..self.clone()
+        }
+    }
+
+    /// Bokeh shape
+    pub fn with_aperture_shape(mut self, shape: ApertureShape) -> Self {
+        self.aperture_shape = shape;
+        self
+    }
+
+    /// Optical vignetting: off axis, the aperture is clipped by the lens
+    /// barrel, seen as a circle shifted by `strength` aperture radii at
+    /// the image corners. Bokeh turns into cat's eyes and the image
+    /// darkens towards the corners.
+    pub fn with_cat_eye(mut self, strength: f64) -> Self {
+        self.cat_eye = strength;
+        self
+    }
+
+    pub fn with_vignetting(mut self, vignetting: bool) -> Self {
+        self.vignetting = vignetting;
+        self
+    }
+
+    /// Off-axis projection: the image window slides by `x` image widths
+    /// and `y` image heights while the view direction stays the same.
+    /// With a level camera, vertical lines stay parallel.
+    pub fn with_lens_shift(mut self, x: f64, y: f64) -> Self {
+        self.lower_left_corner = self.lower_left_corner
+            + self.horizontal * (x - self.shift.0)
+            + self.vertical * (y - self.shift.1);
+        self.shift = (x, y);
+        self
+    }
+
+    /// Scheimpflug principle: the plane of focus is rotated by `tilt`
+    /// radians around the horizontal axis, a positive tilt bringing its
+    /// bottom closer like the ground, then by `swing` around the vertical
+    /// one, a positive swing bringing its right side closer. It still goes
+    /// through the point in front of the camera at the focus distance.
+    pub fn with_focus_tilt(mut self, tilt: f64, swing: f64) -> Self {
+        let normal =
+            (-self.w * tilt.cos() - self.v * tilt.sin()) * swing.cos() + self.u * swing.sin();
+        let point = self.origin - self.w * self.focus_dist;
+        self.focus_plane = match (tilt, swing) {
+            (t, s) if t == 0.0 && s == 0.0 => None,
+            _ => Some((point, normal)),
+        };
+        self
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        let lens = self.aperture_shape.sample(sampler.get_2d());
+        if self.cat_eye > 0.0 {
+            // Image position, the corners being at distance 1
+            let (width, height) = (self.horizontal.length(), self.vertical.length());
+            let half_diagonal = (width * width + height * height).sqrt() / 2.0;
+            let position = Vec3::with_values(
+                (s - 0.5 + self.shift.0) * width,
+                (t - 0.5 + self.shift.1) * height,
+                0.0,
+            );
+            let barrel = position * (self.cat_eye / half_diagonal);
+            if (lens - barrel).length_squared() > 1.0 {
+                return None;
+            }
+        }
+
+        let rd = lens * self.lens_radius;
+        let offset = self.u * rd.x() + self.v * rd.y();
+        let mut target = self.lower_left_corner + self.horizontal * s + self.vertical * t;
+        if let Some((point, normal)) = self.focus_plane {
+            // Where the ray through the lens center meets the plane of focus
+            let chief = target - self.origin;
+            let distance = (point - self.origin).dot(normal) / chief.dot(normal);
+            if distance > 0.0 {
+                target = self.origin + chief * distance;
+            }
+        }
+        let direction = target - self.origin - offset;
+
+        // Rays are dropped rather than weighted, the camera having no
+        // way to return a weight
+        if self.vignetting {
+            let cos_theta = -direction.unit_vec().dot(self.w);
+            if sampler.get_1d() > cos_theta.powi(4) {
+                return None;
+            }
+        }
+        Some(Ray::new(self.origin + offset, direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PerspectiveCamera;
+    use crate::libcore::camera::Camera;
+    use crate::libcore::sampler::{IndependentSampler, Sampler};
+    use crate::math::{Point3, Vec3};
+
+    /// Every ray leaving the lens towards a point of the image
+    /// meets the others on the tilted plane of focus
+    #[test]
+    fn tilted_focus_plane() {
+        let camera = PerspectiveCamera::new(
+            Point3::with_values(0.0, 1.0, 0.0),
+            Point3::with_values(0.0, 1.0, -1.0),
+            Vec3::with_values(0.0, 1.0, 0.0),
+            60.0,
+            1.5,
+            0.5,
+            5.0,
+        )
+        .with_focus_tilt(std::f64::consts::FRAC_PI_4, 0.0);
+        let point = Point3::with_values(0.0, 1.0, -5.0);
+        let normal = Vec3::with_values(0.0, -1.0, -1.0);
+        let mut sampler = IndependentSampler::new(0);
+        for pixel in 0..4 {
+            let mut hits = Vec::new();
+            for i in 0..8 {
+                sampler.start_pixel_sample((pixel, 0), i);
+                let ray = camera.get_ray(0.2 * pixel as f64 + 0.1, 0.2, &mut sampler).unwrap();
+                hits.push(ray.at((point - ray.origin).dot(normal) / ray.direction.dot(normal)));
+            }
+            for p in &hits {
+                assert!((*p - hits[0]).length() < 1e-9, "{:?} {:?}", p, hits[0]);
+            }
+        }
+    }
+}
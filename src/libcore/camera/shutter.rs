@@ -0,0 +1,42 @@
+use super::Camera;
+use crate::libcore::sampler::Sampler;
+use crate::math::{lerp, Ray};
+
+/// Interval during which the film is exposed, in scene time units
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Shutter {
+    pub open: f64,
+    pub close: f64,
+}
+
+impl Shutter {
+    pub fn new(open: f64, close: f64) -> Self {
+        Shutter { open, close }
+    }
+
+    /// Time of a ray for the uniform sample `u`
+    pub fn sample(&self, u: f64) -> f64 {
+        lerp(&self.open, &self.close, u)
+    }
+}
+
+/// Spreads the rays of `camera` over the shutter interval so that
+/// moving objects get motion blurred
+pub struct ShutterCamera<C: Camera + ?Sized> {
+    camera: Box<C>,
+    shutter: Shutter,
+}
+
+impl<C: Camera + ?Sized> ShutterCamera<C> {
+    pub fn new(camera: Box<C>, shutter: Shutter) -> Self {
+        ShutterCamera { camera, shutter }
+    }
+}
+
+impl<C: Camera + ?Sized> Camera for ShutterCamera<C> {
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        let mut ray = self.camera.get_ray(s, t, sampler)?;
+        ray.time = self.shutter.sample(sampler.get_1d());
+        Some(ray)
+    }
+}
@@ -0,0 +1,278 @@
+use super::panoramic::{equirectangular_angles, ViewFrame};
+use super::{Camera, PerspectiveCamera};
+use crate::color::Color;
+use crate::libcore::sampler::Sampler;
+use crate::math::{Point3, Ray, Vec3};
+
+/// How both eyes share the output image
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StereoLayout {
+    /// Left eye on the left half, right eye on the right half
+    SideBySide,
+    /// Left eye on the top half, right eye on the bottom half
+    OverUnder,
+    /// Red-cyan glasses image, rendered side by side then combined
+    /// with `compose_anaglyph`
+    Anaglyph,
+}
+
+impl StereoLayout {
+    /// Size of the rendered image for eyes of `width` x `height` pixels
+    pub fn render_size(&self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            StereoLayout::SideBySide | StereoLayout::Anaglyph => (width * 2, height),
+            StereoLayout::OverUnder => (width, height * 2),
+        }
+    }
+}
+
+/// Stereo rig rendering both eyes in the same image
+/// according to `layout`
+pub struct StereoCamera<C: Camera> {
+    left: C,
+    right: C,
+    layout: StereoLayout,
+    /// Width and height of an eye in pixels
+    eye_size: (usize, usize),
+}
+
+impl<C: Camera> StereoCamera<C> {
+    pub fn new(left: C, right: C, layout: StereoLayout, eye_size: (usize, usize)) -> Self {
+        StereoCamera {
+            left,
+            right,
+            layout,
+            eye_size,
+        }
+    }
+}
+
+impl StereoCamera<PerspectiveCamera> {
+    /// Parallel eyes `interocular` apart around `center`, their frustums
+    /// shifted (off-axis) so that they overlap exactly at `convergence`
+    /// distance. Objects at that distance appear on the screen plane.
+    pub fn perspective(
+        center: &PerspectiveCamera,
+        interocular: f64,
+        convergence: f64,
+        layout: StereoLayout,
+        eye_size: (usize, usize),
+    ) -> Self {
+        StereoCamera::new(
+            center.with_eye_offset(-interocular / 2.0, convergence),
+            center.with_eye_offset(interocular / 2.0, convergence),
+            layout,
+            eye_size,
+        )
+    }
+}
+
+impl StereoCamera<OdsCamera> {
+    /// Omni-directional stereo panorama: equirectangular eyes whose
+    /// position rotates with the viewing direction
+    pub fn omnidirectional(
+        lookfrom: Point3<f64>,
+        lookat: Point3<f64>,
+        vup: Vec3<f64>,
+        interocular: f64,
+        convergence: f64,
+        layout: StereoLayout,
+        eye_size: (usize, usize),
+    ) -> Self {
+        StereoCamera::new(
+            OdsCamera::new(lookfrom, lookat, vup, -interocular / 2.0, convergence),
+            OdsCamera::new(lookfrom, lookat, vup, interocular / 2.0, convergence),
+            layout,
+            eye_size,
+        )
+    }
+}
+
+impl<C: Camera> Camera for StereoCamera<C> {
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        // s and t run over the image size minus one pixel like those
+        // of each eye, which are recovered from the pixel coordinates
+        let (width, height) = self.eye_size;
+        match self.layout {
+            StereoLayout::SideBySide | StereoLayout::Anaglyph => {
+                match split(s * (2 * width - 1) as f64, width) {
+                    (true, s) => self.left.get_ray(s, t, sampler),
+                    (false, s) => self.right.get_ray(s, t, sampler),
+                }
+            }
+            StereoLayout::OverUnder => match split(t * (2 * height - 1) as f64, height) {
+                (true, t) => self.right.get_ray(s, t, sampler),
+                (false, t) => self.left.get_ray(s, t, sampler),
+            },
+        }
+    }
+}
+
+/// Whether the pixel coordinate `x` of an axis holding two eyes of
+/// `size` pixels falls in the first one, and its image coordinate
+/// in that eye. Eyes are split on whole pixels.
+fn split(x: f64, size: usize) -> (bool, f64) {
+    let first = (x as usize) < size;
+    let x = match first {
+        true => x,
+        false => x - size as f64,
+    };
+    (first, x / (size - 1) as f64)
+}
+
+/// One eye of an omni-directional stereo panorama. Every column
+/// sees the scene from a point of the circle of diameter the
+/// interocular distance, tangent to the viewing direction.
+pub struct OdsCamera {
+    frame: ViewFrame,
+    /// Signed distance of the eye to the rig center, negative on the left
+    eye_offset: f64,
+    convergence: f64,
+}
+
+impl OdsCamera {
+    pub fn new(
+        lookfrom: Point3<f64>,
+        lookat: Point3<f64>,
+        vup: Vec3<f64>,
+        eye_offset: f64,
+        convergence: f64,
+    ) -> Self {
+        OdsCamera {
+            frame: ViewFrame::new(lookfrom, lookat, vup),
+            eye_offset,
+            convergence,
+        }
+    }
+}
+
+impl Camera for OdsCamera {
+    fn get_ray(&self, s: f64, t: f64, _: &mut dyn Sampler) -> Option<Ray> {
+        let (phi, theta) = equirectangular_angles(s, t);
+        let f = &self.frame;
+        let direction = f.spherical(phi, theta);
+        // Right hand side of the viewer looking at longitude phi
+        let side = f.right * phi.cos() - f.forward * phi.sin();
+        let eye = f.origin + side * self.eye_offset;
+        // Both eyes look at the same point at the convergence distance
+        let target = f.origin + direction * self.convergence;
+        Some(Ray::new(eye, target - eye))
+    }
+}
+
+/// Merges a side by side render into a red-cyan anaglyph: red from
+/// the left eye, green and blue from the right one
+pub fn compose_anaglyph(pixels: &[Color<f64>], width: usize, height: usize) -> Vec<Color<f64>> {
+    let eye_width = width / 2;
+    let mut out = Vec::with_capacity(eye_width * height);
+    for y in 0..height {
+        for x in 0..eye_width {
+            let left = pixels[y * width + x];
+            let right = pixels[y * width + eye_width + x];
+            out.push(Color::with_values(left.x(), right.y(), right.z()));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compose_anaglyph, StereoCamera, StereoLayout};
+    use crate::color::Color;
+    use crate::libcore::camera::{Camera, PerspectiveCamera};
+    use crate::libcore::sampler::IndependentSampler;
+    use crate::math::{Point3, Ray, Vec3};
+
+    const EYE: (usize, usize) = (6, 4);
+
+    fn center() -> PerspectiveCamera {
+        PerspectiveCamera::new(
+            Point3::new(),
+            Point3::with_values(0.0, 0.0, -1.0),
+            Vec3::with_values(0.0, 1.0, 0.0),
+            40.0,
+            1.5,
+            0.0,
+            10.0,
+        )
+    }
+
+    /// Ray through the pixel (i, j) from the top left corner,
+    /// as sampled by the renderer
+    fn ray(camera: &dyn Camera, size: (usize, usize), i: f64, j: f64) -> Ray {
+        let s = i / (size.0 - 1) as f64;
+        let t = (size.1 as f64 - j) / (size.1 - 1) as f64;
+        camera
+            .get_ray(s, t, &mut IndependentSampler::new(0))
+            .unwrap()
+    }
+
+    fn assert_same(a: &Ray, b: &Ray) {
+        assert!((a.origin - b.origin).length() < 1e-9);
+        assert!((a.direction.unit_vec() - b.direction.unit_vec()).length() < 1e-9);
+    }
+
+    #[test]
+    fn eyes_match_a_single_camera_without_interocular() {
+        let center = center();
+        let layouts = [
+            (StereoLayout::SideBySide, (EYE.0 as f64, 0.0)),
+            (StereoLayout::OverUnder, (0.0, EYE.1 as f64)),
+        ];
+        for &(layout, (di, dj)) in layouts.iter() {
+            let stereo = StereoCamera::perspective(&center, 0.0, 10.0, layout, EYE);
+            let size = layout.render_size(EYE.0, EYE.1);
+            // Pixels of the left eye, then the same pixels in the right one
+            for &(i, j) in [(0.2, 0.1), (2.5, 1.5), (5.99, 3.99)].iter() {
+                let expected = ray(&center, EYE, i, j);
+                assert_same(&ray(&stereo, size, i, j), &expected);
+                assert_same(&ray(&stereo, size, i + di, j + dj), &expected);
+            }
+        }
+    }
+
+    #[test]
+    fn eyes_are_offset_sideways() {
+        let center = center();
+        let stereo = StereoCamera::perspective(&center, 0.1, 10.0, StereoLayout::SideBySide, EYE);
+        let size = StereoLayout::SideBySide.render_size(EYE.0, EYE.1);
+        let left = ray(&stereo, size, 3.0, 2.0);
+        let right = ray(&stereo, size, 9.0, 2.0);
+        assert!((right.origin - left.origin - Vec3::with_values(0.1, 0.0, 0.0)).length() < 1e-9);
+        // Both look at the same point at the convergence distance
+        let at = |r: &Ray| r.origin + r.direction * (-10.0 / r.direction.z());
+        assert!((at(&left) - at(&right)).length() < 1e-9);
+    }
+
+    #[test]
+    fn omnidirectional_eyes_circle_the_center() {
+        let stereo = StereoCamera::omnidirectional(
+            Point3::new(),
+            Point3::with_values(0.0, 0.0, -1.0),
+            Vec3::with_values(0.0, 1.0, 0.0),
+            0.1,
+            10.0,
+            StereoLayout::OverUnder,
+            EYE,
+        );
+        let size = StereoLayout::OverUnder.render_size(EYE.0, EYE.1);
+        for &i in [0.5, 2.0, 4.5].iter() {
+            let left = ray(&stereo, size, i, 2.0);
+            let right = ray(&stereo, size, i, 6.0);
+            assert!((left.origin.length() - 0.05).abs() < 1e-9);
+            assert!((left.origin + right.origin).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn anaglyph_takes_red_from_the_left_eye() {
+        let left = Color::with_values(1.0, 0.2, 0.3);
+        let right = Color::with_values(0.4, 0.5, 0.6);
+        let pixels = vec![left, right, left, right];
+        let out = compose_anaglyph(&pixels, 2, 2);
+        assert_eq!(out.len(), 2);
+        for c in out {
+            assert_eq!((c.x(), c.y(), c.z()), (1.0, 0.5, 0.6));
+        }
+    }
+}
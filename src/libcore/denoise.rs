@@ -0,0 +1,222 @@
+use super::aov::{AovLayer, FrameBuffer};
+use crate::color::Color;
+use crate::math::Vec3;
+use rayon::prelude::*;
+
+/// B3 spline used by the à-trous wavelet transform
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Albedo below this value is not divided out of the beauty pass
+const MIN_ALBEDO: f64 = 0.01;
+
+#[derive(Clone, Copy, Debug)]
+pub struct DenoiseSettings {
+    /// Number of à-trous passes, the filter footprint doubles with each one
+    pub iterations: usize,
+    /// Tolerance on the (albedo demodulated) color difference,
+    /// shrunk at every pass so that details survive
+    pub sigma_color: f64,
+    /// Exponent applied to the normals dot product
+    pub sigma_normal: f64,
+    /// Tolerance on depth differences relative to the pixel depth
+    pub sigma_depth: f64,
+    pub sigma_albedo: f64,
+}
+
+impl Default for DenoiseSettings {
+    fn default() -> Self {
+        DenoiseSettings {
+            iterations: 5,
+            sigma_color: 4.0,
+            sigma_normal: 64.0,
+            sigma_depth: 0.05,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+/// Edge-avoiding à-trous wavelet filter (as in SVGF) guided by the albedo,
+/// normal and depth passes of `frame`. Lighting is divided by the albedo
+/// before filtering so that textures are kept sharp, then multiplied back.
+/// Returns the linear denoised beauty pass.
+pub fn denoise(frame: &FrameBuffer, settings: &DenoiseSettings) -> Vec<Color<f64>> {
+    let guide = Guide {
+        width: frame.width,
+        height: frame.height,
+        albedo: frame.layer(AovLayer::Albedo),
+        normal: frame.layer(AovLayer::Normal),
+        depth: frame.layer(AovLayer::Depth).iter().map(|d| d.x()).collect(),
+    };
+
+    let beauty = frame.layer(AovLayer::Beauty);
+    let mut irradiance: Vec<Color<f64>> = beauty
+        .iter()
+        .zip(&guide.albedo)
+        .map(|(c, a)| demodulate(c, a))
+        .collect();
+
+    let mut sigma_color = settings.sigma_color;
+    for i in 0..settings.iterations {
+        irradiance = guide.filter(&irradiance, 1 << i, sigma_color, settings);
+        sigma_color *= 0.5;
+    }
+
+    irradiance
+        .iter()
+        .zip(&guide.albedo)
+        .map(|(c, a)| remodulate(c, a))
+        .collect()
+}
+
+struct Guide {
+    width: usize,
+    height: usize,
+    albedo: Vec<Color<f64>>,
+    normal: Vec<Vec3<f64>>,
+    depth: Vec<f64>,
+}
+
+impl Guide {
+    fn filter(
+        &self,
+        input: &[Color<f64>],
+        step: usize,
+        sigma_color: f64,
+        settings: &DenoiseSettings,
+    ) -> Vec<Color<f64>> {
+        let mut output = vec![Color::new(); input.len()];
+        output
+            .par_chunks_mut(self.width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, out) in row.iter_mut().enumerate() {
+                    *out = self.filter_pixel(input, x, y, step, sigma_color, settings);
+                }
+            });
+        output
+    }
+
+    fn filter_pixel(
+        &self,
+        input: &[Color<f64>],
+        x: usize,
+        y: usize,
+        step: usize,
+        sigma_color: f64,
+        settings: &DenoiseSettings,
+    ) -> Color<f64> {
+        let p = y * self.width + x;
+        let mut sum = Color::new();
+        let mut weights = 0.0;
+
+        for (ky, ky_weight) in KERNEL.iter().enumerate() {
+            let qy = y as isize + (ky as isize - 2) * step as isize;
+            if qy < 0 || qy >= self.height as isize {
+                continue;
+            }
+            for (kx, kx_weight) in KERNEL.iter().enumerate() {
+                let qx = x as isize + (kx as isize - 2) * step as isize;
+                if qx < 0 || qx >= self.width as isize {
+                    continue;
+                }
+                let q = qy as usize * self.width + qx as usize;
+
+                let w = ky_weight
+                    * kx_weight
+                    * self.edge_weight(p, q, settings)
+                    * (-(input[p] - input[q]).length() / sigma_color).exp();
+                sum += &(input[q] * w);
+                weights += w;
+            }
+        }
+
+        match weights {
+            w if w > 0.0 => sum / w,
+            _ => input[p],
+        }
+    }
+
+    /// How much pixel `q` is considered to belong to the same surface as `p`
+    fn edge_weight(&self, p: usize, q: usize, settings: &DenoiseSettings) -> f64 {
+        let (zp, zq) = (self.depth[p], self.depth[q]);
+        let w_depth = match (zp.is_finite(), zq.is_finite()) {
+            (true, true) => (-(zp - zq).abs() / (settings.sigma_depth * zp.max(1e-4))).exp(),
+            (false, false) => 1.0,
+            _ => return 0.0,
+        };
+        let w_normal = self.normal[p]
+            .dot(self.normal[q])
+            .max(0.0)
+            .powf(settings.sigma_normal);
+        let w_albedo = (-(self.albedo[p] - self.albedo[q]).length_squared()
+            / (settings.sigma_albedo * settings.sigma_albedo))
+            .exp();
+
+        // Both being the sky, normals are zero
+        match (zp.is_finite(), zq.is_finite()) {
+            (false, false) => w_albedo,
+            _ => w_depth * w_normal * w_albedo,
+        }
+    }
+}
+
+fn demodulate(c: &Color<f64>, albedo: &Color<f64>) -> Color<f64> {
+    let div = |v: f64, a: f64| if a > MIN_ALBEDO { v / a } else { v };
+    Color::with_values(
+        div(c.x(), albedo.x()),
+        div(c.y(), albedo.y()),
+        div(c.z(), albedo.z()),
+    )
+}
+
+fn remodulate(c: &Color<f64>, albedo: &Color<f64>) -> Color<f64> {
+    let mul = |v: f64, a: f64| if a > MIN_ALBEDO { v * a } else { v };
+    Color::with_values(
+        mul(c.x(), albedo.x()),
+        mul(c.y(), albedo.y()),
+        mul(c.z(), albedo.z()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{denoise, DenoiseSettings};
+    use crate::color::Color;
+    use crate::libcore::aov::{AovSample, FrameBuffer};
+    use crate::libcore::film::{Filter, FilterKind};
+    use crate::math::Vec3;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Two flat walls facing different directions with noisy lighting
+    fn noisy_frame() -> FrameBuffer {
+        let mut frame = FrameBuffer::new(32, 16, Filter::with_default_radius(FilterKind::Box));
+        let mut rng = StdRng::seed_from_u64(7);
+        for i in 0..32 * 16 {
+            let left = i % 32 < 16;
+            let mut sample = AovSample::new();
+            let level = if left { 0.2 } else { 0.8 };
+            let noisy = level + (rng.gen::<f64>() - 0.5) * 0.3;
+            sample.beauty = Color::with_values(noisy, noisy, noisy);
+            sample.albedo = Color::with_values(1.0, 1.0, 1.0);
+            sample.normal = if left {
+                Vec3::with_values(1.0, 0.0, 0.0)
+            } else {
+                Vec3::with_values(0.0, 0.0, 1.0)
+            };
+            sample.depth = Some(1.0);
+            frame.add_sample(i % 32, i / 32, &sample);
+        }
+        frame
+    }
+
+    #[test]
+    fn smooths_noise_but_keeps_edges() {
+        let frame = noisy_frame();
+        let out = denoise(&frame, &DenoiseSettings::default());
+        for (i, c) in out.iter().enumerate() {
+            let expected = if i % 32 < 16 { 0.2 } else { 0.8 };
+            assert!((c.x() - expected).abs() < 0.1, "{:?} at {}", c, i);
+        }
+    }
+}
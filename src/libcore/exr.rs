@@ -0,0 +1,177 @@
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::Path;
+
+const MAGIC: u32 = 20000630;
+const VERSION: u32 = 2;
+const PIXEL_TYPE_FLOAT: i32 = 2;
+
+/// Minimal OpenEXR writer: single part, scanline based, uncompressed
+/// 32 bit float channels. Channels are given as (name, values) with
+/// values stored row by row from the top of the image, dotted names
+/// like `albedo.R` group them into layers.
+pub fn write_exr(
+    path: &Path,
+    width: usize,
+    height: usize,
+    mut channels: Vec<(String, Vec<f32>)>,
+) -> Result<()> {
+    // The spec requires channels to be sorted by name
+    channels.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut header = Vec::new();
+    let mut chlist = Vec::new();
+    for (name, _) in &channels {
+        chlist.extend_from_slice(name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        // pLinear and reserved bytes
+        chlist.extend_from_slice(&[0, 0, 0, 0]);
+        // x and y sampling
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+    }
+    chlist.push(0);
+    attribute(&mut header, "channels", "chlist", &chlist);
+    attribute(&mut header, "compression", "compression", &[0]);
+    let window = box2i(width, height);
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut header, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+
+    let line_size = width * channels.len() * 4;
+    // y coordinate and data size come before every scanline
+    let block_size = 8 + line_size;
+    let first_block = 8 + header.len() + height * 8;
+
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&MAGIC.to_le_bytes())?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&header)?;
+    for y in 0..height {
+        out.write_all(&((first_block + y * block_size) as u64).to_le_bytes())?;
+    }
+    for y in 0..height {
+        out.write_all(&(y as i32).to_le_bytes())?;
+        out.write_all(&(line_size as i32).to_le_bytes())?;
+        for (_, values) in &channels {
+            for v in &values[y * width..(y + 1) * width] {
+                out.write_all(&v.to_le_bytes())?;
+            }
+        }
+    }
+    out.flush()
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+fn box2i(width: usize, height: usize) -> Vec<u8> {
+    [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_exr, MAGIC, VERSION};
+    use std::convert::TryInto;
+    use std::fs;
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    /// Null terminated string at `at` and the offset past it
+    fn string_at(bytes: &[u8], at: usize) -> (String, usize) {
+        let end = at + bytes[at..].iter().position(|&b| b == 0).unwrap();
+        (
+            String::from_utf8_lossy(&bytes[at..end]).into_owned(),
+            end + 1,
+        )
+    }
+
+    #[test]
+    fn scanline_file_layout() {
+        let path = std::env::temp_dir().join(format!("tracer-{}.exr", std::process::id()));
+        let (width, height) = (3, 2);
+        let channels = vec![
+            ("G".to_string(), (0..6).map(|v| v as f32).collect()),
+            ("B".to_string(), (0..6).map(|v| 10.0 + v as f32).collect()),
+        ];
+        write_exr(&path, width, height, channels).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(u32_at(&bytes, 0), MAGIC);
+        assert_eq!(u32_at(&bytes, 4), VERSION);
+
+        let mut attributes = Vec::new();
+        let mut at = 8;
+        while bytes[at] != 0 {
+            let (name, next) = string_at(&bytes, at);
+            let (kind, next) = string_at(&bytes, next);
+            let size = u32_at(&bytes, next) as usize;
+            attributes.push((name, kind, bytes[next + 4..next + 4 + size].to_vec()));
+            at = next + 4 + size;
+        }
+        at += 1;
+        let names: Vec<&str> = attributes.iter().map(|a| a.0.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "channels",
+                "compression",
+                "dataWindow",
+                "displayWindow",
+                "lineOrder",
+                "pixelAspectRatio",
+                "screenWindowCenter",
+                "screenWindowWidth"
+            ]
+        );
+        // Sorted by name, 16 bytes after each one, and a closing null
+        let chlist = &attributes[0].2;
+        assert_eq!(&chlist[..2], b"B\0");
+        assert_eq!(&chlist[18..20], b"G\0");
+        assert_eq!(chlist.len(), 2 * 18 + 1);
+        let window: Vec<u32> = (0..4).map(|i| u32_at(&attributes[2].2, 4 * i)).collect();
+        assert_eq!(window, [0, 0, 2, 1]);
+
+        // Offsets point to scanlines holding all of B then all of G
+        let line_size = width * 2 * 4;
+        for y in 0..height {
+            let offset = u64::from_le_bytes(bytes[at + 8 * y..at + 8 * y + 8].try_into().unwrap());
+            let block = offset as usize;
+            assert_eq!(u32_at(&bytes, block), y as u32);
+            assert_eq!(u32_at(&bytes, block + 4) as usize, line_size);
+            let values: Vec<f32> = (0..2 * width)
+                .map(|i| f32::from_bits(u32_at(&bytes, block + 8 + 4 * i)))
+                .collect();
+            let row = (3 * y) as f32;
+            assert_eq!(
+                values,
+                [
+                    10.0 + row,
+                    11.0 + row,
+                    12.0 + row,
+                    row,
+                    row + 1.0,
+                    row + 2.0
+                ]
+            );
+        }
+        assert_eq!(bytes.len(), at + height * (8 + 8 + line_size));
+    }
+}
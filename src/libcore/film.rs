@@ -0,0 +1,253 @@
+use crate::color::Color;
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    Gaussian,
+    /// Mitchell-Netravali with B = C = 1/3
+    Mitchell,
+    /// Sinc windowed by a wider sinc, as many lobes as the radius
+    Lanczos,
+}
+
+/// Pixel reconstruction filter, separable along x and y
+#[derive(Clone, Copy, Debug)]
+pub struct Filter {
+    pub kind: FilterKind,
+    /// Extent of the filter in pixels around the pixel center
+    pub radius: f64,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, radius: f64) -> Self {
+        Filter { kind, radius }
+    }
+
+    /// Filter with its usual radius
+    pub fn with_default_radius(kind: FilterKind) -> Self {
+        let radius = match kind {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::Lanczos => 3.0,
+        };
+        Filter::new(kind, radius)
+    }
+
+    /// Weight of a sample at offset (dx, dy) from a pixel center
+    pub fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, x: f64) -> f64 {
+        let r = self.radius;
+        let x = x.abs();
+        if x > r {
+            return 0.0;
+        }
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => r - x,
+            FilterKind::Gaussian => {
+                let sigma = r / 3.0;
+                let gaussian = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+                f64::max(0.0, gaussian(x) - gaussian(r))
+            }
+            FilterKind::Mitchell => mitchell(2.0 * x / r),
+            FilterKind::Lanczos => sinc(x) * sinc(x / r),
+        }
+    }
+}
+
+fn mitchell(x: f64) -> f64 {
+    let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+    let x = x.abs();
+    let v = if x > 1.0 {
+        (-b - 6.0 * c) * x.powi(3)
+            + (6.0 * b + 30.0 * c) * x.powi(2)
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+    } else {
+        (12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+            + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+            + (6.0 - 2.0 * b)
+    };
+    v / 6.0
+}
+
+fn sinc(x: f64) -> f64 {
+    match x {
+        x if x.abs() < 1e-5 => 1.0,
+        x => (PI * x).sin() / (PI * x),
+    }
+}
+
+/// Weighted sum of the samples contributing to a pixel
+#[derive(Clone, Copy)]
+pub struct FilmPixel {
+    sum: Color<f64>,
+    weight: f64,
+    /// Contributions that are not weighted by the filter,
+    /// like the ones coming from light tracing
+    splat: Color<f64>,
+}
+
+impl FilmPixel {
+    fn new() -> Self {
+        FilmPixel {
+            sum: Color::new(),
+            weight: 0.0,
+            splat: Color::new(),
+        }
+    }
+}
+
+/// Image plane accumulating radiance samples through a reconstruction
+/// filter, every sample contributing to all the pixels within the
+/// filter radius. Rendering threads fill their own `FilmTile`s which
+/// are then merged back.
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+    pub filter: Filter,
+    pixels: Vec<FilmPixel>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize, filter: Filter) -> Self {
+        Film {
+            width,
+            height,
+            filter,
+            pixels: vec![FilmPixel::new(); width * height],
+        }
+    }
+
+    /// Tile receiving the samples taken in rows `top..top + rows`
+    pub fn tile(&self, top: usize, rows: usize) -> FilmTile {
+        let margin = self.filter.radius.ceil() as usize;
+        let y0 = top.saturating_sub(margin);
+        let y1 = usize::min(top + rows + margin, self.height);
+        FilmTile {
+            width: self.width,
+            y0,
+            y1,
+            filter: self.filter,
+            pixels: vec![FilmPixel::new(); self.width * (y1 - y0)],
+        }
+    }
+
+    pub fn merge(&mut self, tile: &FilmTile) {
+        let offset = tile.y0 * self.width;
+        for (pixel, t) in self.pixels[offset..].iter_mut().zip(&tile.pixels) {
+            pixel.sum += &t.sum;
+            pixel.weight += t.weight;
+            pixel.splat += &t.splat;
+        }
+    }
+
+    /// Adds a contribution at raster position `p` without filter
+    /// weighting, `splat_scale` in `resolve` normalizing them
+    pub fn add_splat(&mut self, p: (f64, f64), radiance: &Color<f64>) {
+        let (x, y) = (p.0.floor(), p.1.floor());
+        if x < 0.0 || y < 0.0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        self.pixels[y as usize * self.width + x as usize].splat += radiance;
+    }
+
+    /// Final linear color of every pixel
+    pub fn resolve(&self, splat_scale: f64) -> Vec<Color<f64>> {
+        self.pixels
+            .iter()
+            .map(|p| {
+                let filtered = match p.weight {
+                    w if w > 0.0 => p.sum / w,
+                    _ => Color::new(),
+                };
+                filtered + p.splat * splat_scale
+            })
+            .collect()
+    }
+}
+
+/// Horizontal band of the film, large enough to hold
+/// the filter footprint of the rows it was created for
+pub struct FilmTile {
+    width: usize,
+    y0: usize,
+    y1: usize,
+    filter: Filter,
+    pixels: Vec<FilmPixel>,
+}
+
+impl FilmTile {
+    /// Adds a sample taken at raster position `p`, pixel (i, j) covering
+    /// [i, i + 1) x [j, j + 1) with y going down
+    pub fn add_sample(&mut self, p: (f64, f64), radiance: &Color<f64>) {
+        let r = self.filter.radius;
+        // Pixel centers are at half integer coordinates
+        let (px, py) = (p.0 - 0.5, p.1 - 0.5);
+        let x0 = f64::max((px - r).ceil(), 0.0) as usize;
+        let x1 = f64::min((px + r).floor(), (self.width - 1) as f64);
+        let y0 = f64::max((py - r).ceil(), self.y0 as f64) as usize;
+        let y1 = f64::min((py + r).floor(), (self.y1 - 1) as f64);
+        if x1 < 0.0 || y1 < 0.0 {
+            return;
+        }
+
+        for y in y0..=y1 as usize {
+            for x in x0..=x1 as usize {
+                let w = self.filter.evaluate(x as f64 - px, y as f64 - py);
+                if w == 0.0 {
+                    continue;
+                }
+                let pixel = &mut self.pixels[(y - self.y0) * self.width + x];
+                pixel.sum += &(*radiance * w);
+                pixel.weight += w;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Film, Filter, FilterKind};
+    use crate::color::Color;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Weights are normalized per pixel so a constant signal
+    /// comes out unchanged whatever the filter
+    #[test]
+    fn constant_image_is_preserved() {
+        let kinds = [
+            FilterKind::Box,
+            FilterKind::Tent,
+            FilterKind::Gaussian,
+            FilterKind::Mitchell,
+            FilterKind::Lanczos,
+        ];
+        let c = Color::with_values(0.25, 0.5, 1.0);
+        let mut rng = StdRng::seed_from_u64(7);
+        for kind in kinds.iter() {
+            let mut film = Film::new(8, 6, Filter::with_default_radius(*kind));
+            for row in 0..6 {
+                let mut tile = film.tile(row, 1);
+                for i in 0..8 {
+                    for _ in 0..16 {
+                        let p = (i as f64 + rng.gen::<f64>(), row as f64 + rng.gen::<f64>());
+                        tile.add_sample(p, &c);
+                    }
+                }
+                film.merge(&tile);
+            }
+            for pixel in film.resolve(0.0) {
+                assert!((pixel - c).length() < 1e-9, "{:?} {:?}", kind, pixel);
+            }
+        }
+    }
+}
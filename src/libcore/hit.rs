@@ -1,19 +1,59 @@
 use std::sync::Arc;
+use crate::libcore::aabb::Aabb;
 use crate::libcore::material::Material;
+use crate::libcore::sampler::Sampler;
+use crate::math::Onb;
 use crate::math::Point3;
+use crate::math::reflect;
 use crate::math::Ray;
 use crate::math::Vec3;
 
+/// Distance ray origins are pushed off surfaces along the
+/// geometric normal, against self-intersections
+const RAY_OFFSET: f64 = 1e-5;
+
+#[derive(Clone, Copy)]
 pub struct HitRecord<'a> {
     pub p: Point3<f64>,
+    /// Shading normal, facing the incoming ray. It differs from the
+    /// geometric normal under bump or normal maps.
     pub normal: Vec3<f64>,
+    /// Normal of the actual surface, facing the incoming ray
+    pub geometric_normal: Vec3<f64>,
     pub t: f64,
     pub front_face: bool,
+    /// Surface coordinates of the hit, used to look textures up
+    pub u: f64,
+    pub v: f64,
+    /// Derivatives of the position along u and v, zero when the surface
+    /// has no parametrization
+    pub dpdu: Vec3<f64>,
+    pub dpdv: Vec3<f64>,
     pub material: &'a dyn Material,
+    /// Index of the hit object inside the top level list,
+    /// written to the object ID render pass
+    pub object_id: usize,
 }
 
 pub trait Hittable {
     fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitRecord>;
+
+    /// Scattering event of a participating medium along the ray between
+    /// `tmin` and `tmax`, free flights being drawn from `sampler`.
+    /// Integrators ask for it up to the closest surface hit.
+    fn sample_medium(
+        &self,
+        _ray: &Ray,
+        _tmin: f64,
+        _tmax: f64,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    /// Box enclosing the object for every time in `time0..time1`,
+    /// None for unbounded objects
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb>;
 }
 
 impl<'a> HitRecord<'a> {
@@ -51,8 +91,86 @@ impl<'a> HitRecord<'a> {
             p,
             t,
             normal,
+            geometric_normal: normal,
             front_face,
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vec3::new(),
+            dpdv: Vec3::new(),
             material,
+            object_id: 0,
         }
     }
+
+    pub fn with_uv(mut self, u: f64, v: f64) -> Self {
+        self.u = u;
+        self.v = v;
+        self
+    }
+
+    pub fn with_derivatives(mut self, dpdu: Vec3<f64>, dpdv: Vec3<f64>) -> Self {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
+
+    /// Shading frame around the shading normal, `u` following
+    /// the texture u direction when the surface has one
+    pub fn tangent_frame(&self) -> Onb {
+        Onb::from_w_tangent(self.normal, self.dpdu)
+    }
+
+    /// Origin of a ray leaving the hit along `direction`, pushed to the
+    /// side of the surface it goes to
+    pub fn offset_origin(&self, direction: Vec3<f64>) -> Point3<f64> {
+        match direction.dot(self.geometric_normal) > 0.0 {
+            true => self.p + self.geometric_normal * RAY_OFFSET,
+            false => self.p - self.geometric_normal * RAY_OFFSET,
+        }
+    }
+
+    /// `direction` mirrored about the surface when it goes into it.
+    /// Reflections around tilted shading normals stay on the side of
+    /// the incoming ray instead of being lost.
+    pub fn flip_above(&self, direction: Vec3<f64>) -> Vec3<f64> {
+        match direction.dot(self.geometric_normal) < 0.0 {
+            true => reflect(&direction, &self.geometric_normal),
+            false => direction,
+        }
+    }
+}
+
+/// Derivatives of the position along u and v over a triangle, from its
+/// corners and their surface coordinates. Zero for degenerate coordinates.
+pub fn uv_derivatives(p: [Point3<f64>; 3], uv: [(f64, f64); 3]) -> (Vec3<f64>, Vec3<f64>) {
+    let (du1, dv1) = (uv[1].0 - uv[0].0, uv[1].1 - uv[0].1);
+    let (du2, dv2) = (uv[2].0 - uv[0].0, uv[2].1 - uv[0].1);
+    let det = du1 * dv2 - dv1 * du2;
+    if det.abs() < 1e-12 {
+        return (Vec3::new(), Vec3::new());
+    }
+    let (e1, e2) = (p[1] - p[0], p[2] - p[0]);
+    ((e1 * dv2 - e2 * dv1) / det, (e2 * du1 - e1 * du2) / det)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::uv_derivatives;
+    use crate::math::{Point3, Vec3};
+
+    #[test]
+    fn triangle_derivatives_follow_the_uvs() {
+        let dpdu = Vec3::with_values(2.0, 0.0, 1.0);
+        let dpdv = Vec3::with_values(0.0, 3.0, -1.0);
+        let origin = Point3::with_values(1.0, 1.0, 1.0);
+        let uv = [(0.1, 0.2), (0.5, 0.3), (0.2, 0.9)];
+        let p = [
+            origin + dpdu * uv[0].0 + dpdv * uv[0].1,
+            origin + dpdu * uv[1].0 + dpdv * uv[1].1,
+            origin + dpdu * uv[2].0 + dpdv * uv[2].1,
+        ];
+        let (u, v) = uv_derivatives(p, uv);
+        assert!((u - dpdu).length() < 1e-12);
+        assert!((v - dpdv).length() < 1e-12);
+    }
 }
@@ -1,13 +1,15 @@
+use super::aabb::Aabb;
 use super::hit::HitRecord;
 use super::hit::Hittable;
+use super::sampler::Sampler;
 use crate::math::Ray;
 use std::sync::Arc;
 
-pub struct HittableList<T: Hittable + Send + Sync> {
+pub struct HittableList<T: Hittable + Send + Sync + ?Sized> {
     objects: Vec<Arc<T>>,
 }
 
-impl<T: Hittable + Send + Sync> HittableList<T> {
+impl<T: Hittable + Send + Sync + ?Sized> HittableList<T> {
     pub fn add(&mut self, obj: Arc<T>) {
         self.objects.push(obj);
     }
@@ -19,16 +21,46 @@ impl<T: Hittable + Send + Sync> HittableList<T> {
     }
 }
 
-impl<T: Hittable + Send + Sync> Hittable for HittableList<T> {
+impl<T: Hittable + Send + Sync + ?Sized> Hittable for HittableList<T> {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         let mut hit = None;
         let mut closest = t_max;
-        for obj in &self.objects {
-            if let Some(h) = obj.hit(ray, t_min, closest) {
+        for (id, obj) in self.objects.iter().enumerate() {
+            if let Some(mut h) = obj.hit(ray, t_min, closest) {
                 closest = h.t;
+                h.object_id = id;
                 hit = Some(h);
             }
         }
         hit
     }
+
+    fn sample_medium(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord<'_>> {
+        // Overlapping media each draw their own free flight,
+        // the closest one scattering first
+        let mut event = None;
+        let mut closest = t_max;
+        for obj in self.objects.iter() {
+            if let Some(rec) = obj.sample_medium(ray, t_min, closest, sampler) {
+                closest = rec.t;
+                event = Some(rec);
+            }
+        }
+        event
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let mut objects = self.objects.iter();
+        let first = objects.next()?.bounding_box(time0, time1)?;
+        objects.try_fold(first, |acc, obj| {
+            obj.bounding_box(time0, time1)
+                .map(|b| Aabb::surrounding(&acc, &b))
+        })
+    }
 }
@@ -0,0 +1,238 @@
+use super::Material;
+use super::ScatterRecord;
+use crate::color::{luminance, Color};
+use crate::libcore::hit::HitRecord;
+use crate::libcore::sampler::Sampler;
+use crate::libcore::texture::Texture;
+use crate::math::{Onb, Ray, Vec3};
+use std::sync::Arc;
+
+type TextureRef = Arc<dyn Texture + Send + Sync>;
+
+/// Shading normal on the outer side of the surface
+fn outward_normal(rec: &HitRecord) -> Vec3<f64> {
+    match rec.front_face {
+        true => rec.normal,
+        false => -rec.normal,
+    }
+}
+
+/// Hit with the outward shading normal `n`, facing the incoming ray again
+fn with_shading_normal<'a>(rec: &HitRecord<'a>, n: Vec3<f64>) -> HitRecord<'a> {
+    HitRecord {
+        normal: match rec.front_face {
+            true => n,
+            false => -n,
+        },
+        ..*rec
+    }
+}
+
+/// Material shaded as if its surface was displaced by a height map,
+/// the geometry staying untouched. The height is the luminance of the
+/// texture, along the outward normal.
+pub struct BumpMapped<M: Material> {
+    material: M,
+    height: TextureRef,
+    /// Scene units of a texture value of 1
+    scale: f64,
+    /// Offset in surface coordinates of the finite differences
+    step: f64,
+}
+
+impl<M: Material> BumpMapped<M> {
+    pub fn new<T: Texture + Send + Sync + 'static>(material: M, height: T, scale: f64) -> Self {
+        BumpMapped {
+            material,
+            height: Arc::new(height),
+            scale,
+            step: 1.0 / 1024.0,
+        }
+    }
+
+    /// Images looked up at the nearest texel want about one texel
+    pub fn with_step(mut self, step: f64) -> Self {
+        self.step = step;
+        self
+    }
+
+    fn shading<'a>(&self, rec: &HitRecord<'a>) -> HitRecord<'a> {
+        let height = |du: f64, dv: f64| {
+            self.scale * luminance(&self.height.value(rec.u + du, rec.v + dv, &rec.p))
+        };
+        let dhdu = (height(self.step, 0.0) - height(-self.step, 0.0)) / (2.0 * self.step);
+        let dhdv = (height(0.0, self.step) - height(0.0, -self.step)) / (2.0 * self.step);
+
+        // Tangents of the displaced surface p + h n, neglecting
+        // the variation of the normal itself
+        let outward = outward_normal(rec);
+        let n = (rec.dpdu + outward * dhdu).cross(&(rec.dpdv + outward * dhdv));
+        if n.length_squared() < 1e-24 {
+            return with_shading_normal(rec, outward);
+        }
+        let n = n.unit_vec();
+        match n.dot(outward) < 0.0 {
+            true => with_shading_normal(rec, -n),
+            false => with_shading_normal(rec, n),
+        }
+    }
+}
+
+impl<M: Material> Material for BumpMapped<M> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        self.material.scatter(ray, &self.shading(rec), sampler)
+    }
+
+    fn emitted(&self) -> Color<f64> {
+        self.material.emitted()
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: Vec3<f64>, wi: Vec3<f64>) -> Color<f64> {
+        self.material.eval(&self.shading(rec), wo, wi)
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: Vec3<f64>, wi: Vec3<f64>) -> f64 {
+        self.material.pdf(&self.shading(rec), wo, wi)
+    }
+}
+
+/// Material shaded with the normals of a tangent space normal map,
+/// colors [0, 1] mapping to [-1, 1] along dp/du, dp/dv and the outward
+/// normal. Maps should be loaded with `ImageTexture::from_file_linear`.
+pub struct NormalMapped<M: Material> {
+    material: M,
+    normals: TextureRef,
+}
+
+impl<M: Material> NormalMapped<M> {
+    pub fn new<T: Texture + Send + Sync + 'static>(material: M, normals: T) -> Self {
+        NormalMapped {
+            material,
+            normals: Arc::new(normals),
+        }
+    }
+
+    fn shading<'a>(&self, rec: &HitRecord<'a>) -> HitRecord<'a> {
+        // No tangent to orient the map without surface coordinates
+        if rec.dpdu.length_squared() == 0.0 {
+            return *rec;
+        }
+        let frame = Onb::from_w_tangent(outward_normal(rec), rec.dpdu);
+        // Mirrored surface coordinates flip the bitangent
+        let v = match frame.v.dot(rec.dpdv) < 0.0 {
+            true => -frame.v,
+            false => frame.v,
+        };
+
+        let c = self.normals.value(rec.u, rec.v, &rec.p);
+        let n =
+            frame.u * (2.0 * c.x() - 1.0) + v * (2.0 * c.y() - 1.0) + frame.w * (2.0 * c.z() - 1.0);
+        if n.length_squared() < 1e-12 {
+            return *rec;
+        }
+        with_shading_normal(rec, n.unit_vec())
+    }
+}
+
+impl<M: Material> Material for NormalMapped<M> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        self.material.scatter(ray, &self.shading(rec), sampler)
+    }
+
+    fn emitted(&self) -> Color<f64> {
+        self.material.emitted()
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: Vec3<f64>, wi: Vec3<f64>) -> Color<f64> {
+        self.material.eval(&self.shading(rec), wo, wi)
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: Vec3<f64>, wi: Vec3<f64>) -> f64 {
+        self.material.pdf(&self.shading(rec), wo, wi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BumpMapped, NormalMapped};
+    use crate::color::Color;
+    use crate::libcore::hit::HitRecord;
+    use crate::libcore::material::Lambertian;
+    use crate::libcore::texture::Texture;
+    use crate::math::{Point3, Ray, Vec3};
+
+    /// Height growing along u
+    struct Ramp;
+
+    impl Texture for Ramp {
+        fn value(&self, u: f64, _: f64, _: &Point3<f64>) -> Color<f64> {
+            Color::with_values(u, u, u)
+        }
+    }
+
+    /// Hit of the z = 0 plane, its outward normal being +z, from above
+    /// or below
+    fn hit(material: &Lambertian, front_face: bool) -> HitRecord<'_> {
+        let down = match front_face {
+            true => -1.0,
+            false => 1.0,
+        };
+        let ray = Ray::new(
+            Point3::with_values(0.0, 0.0, -down),
+            Vec3::with_values(0.0, 0.0, down),
+        );
+        let outward = Vec3::with_values(0.0, 0.0, 1.0);
+        HitRecord::new_hit(Point3::new(), 1.0, &ray, &outward, material)
+            .with_uv(0.5, 0.5)
+            .with_derivatives(
+                Vec3::with_values(2.0, 0.0, 0.0),
+                Vec3::with_values(0.0, 2.0, 0.0),
+            )
+    }
+
+    #[test]
+    fn bumps_tilt_the_shading_normal_only() {
+        let material = Lambertian::new(Color::new());
+        let hit = |front_face| hit(&material, front_face);
+        let flat = BumpMapped::new(Lambertian::new(Color::new()), 0.3, 1.0);
+        assert!((flat.shading(&hit(true)).normal - hit(true).normal).length() < 1e-12);
+
+        // Slope of 0.5 along x over dp/du = 2
+        let bumped = BumpMapped::new(Lambertian::new(Color::new()), Ramp, 1.0);
+        let expected = Vec3::with_values(-0.5f64, 0.0, 1.0).unit_vec();
+        let rec = bumped.shading(&hit(true));
+        assert!((rec.normal - expected).length() < 1e-9);
+        assert!((rec.geometric_normal - hit(true).geometric_normal).length() < 1e-12);
+        // Seen from inside, the same surface faces the other way
+        let rec = bumped.shading(&hit(false));
+        assert!((rec.normal + expected).length() < 1e-9);
+    }
+
+    #[test]
+    fn normal_maps_follow_the_tangent_frame() {
+        let material = Lambertian::new(Color::new());
+        let hit = |front_face| hit(&material, front_face);
+        let flat = NormalMapped::new(
+            Lambertian::new(Color::new()),
+            Color::with_values(0.5, 0.5, 1.0),
+        );
+        assert!((flat.shading(&hit(true)).normal - hit(true).normal).length() < 1e-12);
+
+        let tilted = NormalMapped::new(
+            Lambertian::new(Color::new()),
+            Color::with_values(0.5, 0.8, 1.0),
+        );
+        let expected = Vec3::with_values(0.0f64, 0.6, 1.0).unit_vec();
+        assert!((tilted.shading(&hit(true)).normal - expected).length() < 1e-9);
+    }
+}
@@ -0,0 +1,140 @@
+use super::microfacet::{fresnel_conductor, TrowbridgeReitz};
+use super::thin_film::ThinFilm;
+use super::Material;
+use super::ScatterRecord;
+use crate::color::spectrum::rgb_to_spectrum;
+use crate::color::Color;
+use crate::libcore::hit::HitRecord;
+use crate::libcore::sampler::Sampler;
+use crate::math::Ray;
+use num::complex::Complex64;
+
+/// Rough metal with a GGX microfacet distribution and the spectral
+/// Fresnel reflectance of its complex index of refraction
+pub struct Conductor {
+    eta: Color<f64>,
+    k: Color<f64>,
+    distribution: TrowbridgeReitz,
+    roughness: f64,
+    thin_film: Option<ThinFilm>,
+}
+
+impl Material for Conductor {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        // Anisotropy stretches along dp/du, or an arbitrary
+        // direction on surfaces without coordinates
+        let frame = rec.tangent_frame();
+        let wo = frame.to_local(-ray_in.direction.unit_vec());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let wm = self.distribution.sample_visible_normal(wo, sampler.get_2d());
+        let cos_theta = wo.dot(wm);
+        let wi = wm * (2.0 * cos_theta) - wo;
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        // The visible normal pdf leaves only the shadowing of wi
+        let shadowing = self.distribution.g2(wo, wi) / self.distribution.g1(wo);
+        let scattered = Ray::with_time(rec.p, frame.local(wi), ray_in.time);
+        let (fresnel, scattered) = match (&self.thin_film, ray_in.wavelength) {
+            (None, _) => (
+                Color::with_values(
+                    fresnel_conductor(cos_theta, self.eta[0], self.k[0]),
+                    fresnel_conductor(cos_theta, self.eta[1], self.k[1]),
+                    fresnel_conductor(cos_theta, self.eta[2], self.k[2]),
+                ),
+                scattered,
+            ),
+            // Exact at the hero wavelength, the ray keeping it
+            (Some(film), Some(lambda)) => {
+                let r = film.reflectance(cos_theta, 1.0, self.ior_at(lambda), lambda);
+                let fresnel = Color::with_values(r, r, r);
+                (fresnel, scattered.with_wavelength(Some(lambda)))
+            }
+            (Some(film), None) => (
+                film.reflectance_rgb(cos_theta, 1.0, |lambda| self.ior_at(lambda)),
+                scattered,
+            ),
+        };
+        Some(ScatterRecord {
+            attenuation: fresnel * shadowing,
+            scattered,
+            specular: true,
+        })
+    }
+}
+
+impl Conductor {
+    /// `eta` and `k` are the real and imaginary parts of the index of
+    /// refraction for red, green and blue, `roughness` in [0, 1]
+    pub fn new(eta: Color<f64>, k: Color<f64>, roughness: f64) -> Conductor {
+        Conductor {
+            eta,
+            k,
+            distribution: TrowbridgeReitz::from_roughness(roughness, 0.0),
+            roughness,
+            thin_film: None,
+        }
+    }
+
+    /// Transparent coating like the oxide layer of anodized metal,
+    /// `thickness` in nanometers
+    pub fn with_thin_film(mut self, thickness: f64, ior: f64) -> Self {
+        self.thin_film = Some(ThinFilm::new(thickness, ior));
+        self
+    }
+
+    /// Complex index of refraction at `wavelength` nanometers,
+    /// interpolated from the red, green and blue ones
+    fn ior_at(&self, wavelength: f64) -> Complex64 {
+        Complex64::new(
+            rgb_to_spectrum(&self.eta, wavelength),
+            rgb_to_spectrum(&self.k, wavelength),
+        )
+    }
+
+    /// Highlights stretched along the tangent, `anisotropy` in [0, 1]
+    pub fn with_anisotropy(mut self, anisotropy: f64) -> Self {
+        self.distribution = TrowbridgeReitz::from_roughness(self.roughness, anisotropy);
+        self
+    }
+
+    pub fn gold(roughness: f64) -> Conductor {
+        Conductor::new(
+            Color::with_values(0.143, 0.374, 1.442),
+            Color::with_values(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Conductor {
+        Conductor::new(
+            Color::with_values(0.155, 0.117, 0.138),
+            Color::with_values(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Conductor {
+        Conductor::new(
+            Color::with_values(0.200, 0.924, 1.102),
+            Color::with_values(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Conductor {
+        Conductor::new(
+            Color::with_values(1.657, 0.880, 0.521),
+            Color::with_values(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+}
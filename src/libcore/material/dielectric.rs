@@ -1,21 +1,93 @@
+use super::microfacet::TrowbridgeReitz;
+use super::thin_film::ThinFilm;
 use super::Material;
 use super::ScatterRecord;
 use crate::color::Color;
 use crate::libcore::hit::HitRecord;
+use crate::libcore::sampler::Sampler;
 use crate::math::{random_in_unit_sphere, reflect, refract, schlick, Ray, Vec3};
-use rand::random;
+use num::complex::Complex64;
 
 pub struct Dielectric {
     ref_idx: f64,
+    /// Microfacet distribution of rough (frosted) interfaces
+    distribution: Option<TrowbridgeReitz>,
+    /// Beer-Lambert absorption coefficient of the interior,
+    /// per scene unit
+    absorption: Color<f64>,
+    /// Index of refraction varying with the wavelength
+    dispersion: Option<Dispersion>,
+    /// Coating of the outer surface
+    thin_film: Option<ThinFilm>,
+}
+
+/// Index of refraction as a function of the wavelength
+#[derive(Clone, Copy, Debug)]
+pub enum Dispersion {
+    /// n = a + b / λ², λ in micrometers
+    Cauchy { a: f64, b: f64 },
+    /// n² = 1 + Σ b λ² / (λ² - c), λ in micrometers
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    /// Borosilicate crown glass
+    pub const BK7: Dispersion = Dispersion::Sellmeier {
+        b: [1.039_612_12, 0.231_792_344, 1.010_469_45],
+        c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+    };
+
+    /// Dense flint glass, strongly dispersive
+    pub const SF10: Dispersion = Dispersion::Sellmeier {
+        b: [1.621_539_02, 0.256_287_842, 1.644_475_52],
+        c: [0.012_224_145_7, 0.059_573_677_5, 147.468_793],
+    };
+
+    pub const DIAMOND: Dispersion = Dispersion::Sellmeier {
+        b: [0.3306, 4.3356, 0.0],
+        c: [0.030_625, 0.011_236, 0.0],
+    };
+
+    /// Index of refraction at `wavelength` nanometers
+    pub fn ior(&self, wavelength: f64) -> f64 {
+        let l2 = (wavelength / 1000.0) * (wavelength / 1000.0);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
-        let attenuation = Color::with_values(1.0, 1.0, 1.0);
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let attenuation = self.interior_transmittance(ray_in, rec);
+        // Spectral rays see the index of their wavelength, and
+        // their directions only hold for that wavelength
+        let (ref_idx, wavelength) = match (&self.dispersion, ray_in.wavelength) {
+            (Some(dispersion), Some(lambda)) => (dispersion.ior(lambda), Some(lambda)),
+            _ => (self.ref_idx, None),
+        };
         let etai_over_eta = match rec.front_face {
-            true => 1.0 / self.ref_idx,
-            false => self.ref_idx,
+            true => 1.0 / ref_idx,
+            false => ref_idx,
         };
+        if let Some(distribution) = &self.distribution {
+            return scatter_rough(distribution, etai_over_eta, ray_in, rec, sampler).map(|s| {
+                ScatterRecord {
+                    attenuation: s.attenuation * attenuation,
+                    scattered: s.scattered.with_wavelength(wavelength),
+                    ..s
+                }
+            });
+        }
         let unit_direction = ray_in.direction.unit_vec();
 
         let cos_theta = f64::min(1.0, rec.normal.dot(-unit_direction));
@@ -23,34 +95,333 @@ impl Material for Dielectric {
         let scattered;
         if etai_over_eta * sin_theta > 1.0 {
             let reflected = reflect(&unit_direction, &rec.normal);
-            scattered = Ray::new(rec.p, reflected);
+            scattered = Ray::with_time(rec.offset_origin(reflected), reflected, ray_in.time)
+                .with_wavelength(wavelength);
             return Some(ScatterRecord {
                 attenuation,
                 scattered,
+                specular: true,
+            });
+        }
+        if let Some(film) = &self.thin_film {
+            // Seen from inside, light crosses the glass then the film
+            let (eta_i, eta_t) = match rec.front_face {
+                true => (1.0, ref_idx),
+                false => (ref_idx, 1.0),
+            };
+            let eta_t = Complex64::new(eta_t, 0.0);
+            let (reflectance, wavelength) = match ray_in.wavelength {
+                Some(lambda) => {
+                    let r = film.reflectance(cos_theta, eta_i, eta_t, lambda);
+                    (Color::with_values(r, r, r), Some(lambda))
+                }
+                None => (
+                    film.reflectance_rgb(cos_theta, eta_i, |_| eta_t),
+                    wavelength,
+                ),
+            };
+            // Reflected or refracted in proportion to the mean
+            // reflectance, colors going to the weights
+            let reflect_prob = (reflectance.x() + reflectance.y() + reflectance.z()) / 3.0;
+            let white = Color::with_values(1.0, 1.0, 1.0);
+            let (direction, weight) = match sampler.get_1d() < reflect_prob {
+                true => (
+                    reflect(&unit_direction, &rec.normal),
+                    reflectance / reflect_prob,
+                ),
+                false => (
+                    refract(&unit_direction, &rec.normal, etai_over_eta),
+                    (white - reflectance) / (1.0 - reflect_prob),
+                ),
+            };
+            return Some(ScatterRecord {
+                attenuation: attenuation * weight,
+                scattered: Ray::with_time(rec.offset_origin(direction), direction, ray_in.time)
+                    .with_wavelength(wavelength),
+                specular: true,
             });
         }
+
         let reflect_prob = schlick(cos_theta, etai_over_eta);
-        if random::<f64>() < reflect_prob {
+        if sampler.get_1d() < reflect_prob {
             let reflected = reflect(&unit_direction, &rec.normal);
-            scattered = Ray::new(rec.p, reflected);
+            scattered = Ray::with_time(rec.offset_origin(reflected), reflected, ray_in.time)
+                .with_wavelength(wavelength);
             return Some(ScatterRecord {
                 attenuation,
                 scattered,
+                specular: true,
             });
         }
 
         let refracted = refract(&unit_direction, &rec.normal, etai_over_eta);
-        scattered = Ray::new(rec.p, refracted);
+        scattered = Ray::with_time(rec.offset_origin(refracted), refracted, ray_in.time)
+            .with_wavelength(wavelength);
 
         return Some(ScatterRecord {
             attenuation,
             scattered,
+            specular: true,
         });
     }
 }
 
 impl Dielectric {
     pub fn new(ref_idx: f64) -> Dielectric {
-        Dielectric { ref_idx }
+        Dielectric {
+            ref_idx,
+            distribution: None,
+            absorption: Color::new(),
+            dispersion: None,
+            thin_film: None,
+        }
+    }
+
+    /// Iridescent coating of the surface, `thickness` in nanometers.
+    /// A soap bubble is a coated dielectric of index 1. Only smooth
+    /// interfaces are coated.
+    pub fn with_thin_film(mut self, thickness: f64, ior: f64) -> Self {
+        self.thin_film = Some(ThinFilm::new(thickness, ior));
+        self
+    }
+
+    /// Glass whose index changes with the wavelength, splitting white
+    /// light in spectral renders. RGB renders use its index at the
+    /// sodium d line.
+    pub fn with_dispersion(mut self, dispersion: Dispersion) -> Self {
+        self.ref_idx = dispersion.ior(587.6);
+        self.dispersion = Some(dispersion);
+        self
+    }
+
+    /// Tinted interior with the absorption coefficient `absorption` per
+    /// scene unit, per channel: light traveling d units inside keeps
+    /// exp(-absorption * d) of its energy
+    pub fn with_absorption(mut self, absorption: Color<f64>) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    /// Tinted interior letting `transmittance` of the light through
+    /// after `distance` scene units, per channel
+    pub fn with_transmittance(self, transmittance: Color<f64>, distance: f64) -> Self {
+        assert!(distance > 0.0, "Transmittance distance must be positive");
+        let coefficient = |t: f64| -f64::max(t, 1e-6).ln() / distance;
+        self.with_absorption(Color::with_values(
+            coefficient(transmittance.x()),
+            coefficient(transmittance.y()),
+            coefficient(transmittance.z()),
+        ))
+    }
+
+    /// Frosted interface, `roughness` in [0, 1], 0 being smooth glass
+    pub fn with_roughness(mut self, roughness: f64) -> Self {
+        self.distribution = match roughness {
+            r if r > 0.0 => Some(TrowbridgeReitz::from_roughness(r, 0.0)),
+            _ => None,
+        };
+        self
+    }
+
+    /// Beer-Lambert attenuation of the path inside the object,
+    /// which ends where the ray leaves it through a back face
+    fn interior_transmittance(&self, ray_in: &Ray, rec: &HitRecord) -> Color<f64> {
+        if rec.front_face {
+            return Color::with_values(1.0, 1.0, 1.0);
+        }
+        let distance = rec.t * ray_in.direction.length();
+        Color::with_values(
+            (-self.absorption.x() * distance).exp(),
+            (-self.absorption.y() * distance).exp(),
+            (-self.absorption.z() * distance).exp(),
+        )
+    }
+}
+
+/// Rough interface (Walter et al. 2007): the smooth interface logic
+/// applied to a visible microfacet normal, the path weight being the
+/// shadowing of the outgoing direction
+fn scatter_rough(
+    distribution: &TrowbridgeReitz,
+    etai_over_eta: f64,
+    ray_in: &Ray,
+    rec: &HitRecord,
+    sampler: &mut dyn Sampler,
+) -> Option<ScatterRecord> {
+    let frame = rec.tangent_frame();
+    let unit_direction = ray_in.direction.unit_vec();
+    let wo = frame.to_local(-unit_direction);
+    if wo.z() <= 0.0 {
+        return None;
+    }
+    let wm = distribution.sample_visible_normal(wo, sampler.get_2d());
+    let microfacet_normal = frame.local(wm);
+
+    let cos_theta = f64::min(1.0, wo.dot(wm));
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    let direction = match etai_over_eta * sin_theta > 1.0 {
+        // Total internal reflection on the microfacet
+        true => reflect(&unit_direction, &microfacet_normal),
+        false if sampler.get_1d() < schlick(cos_theta, etai_over_eta) => {
+            reflect(&unit_direction, &microfacet_normal)
+        }
+        false => refract(&unit_direction, &microfacet_normal, etai_over_eta),
+    };
+
+    // Reflections must leave on the incident side and
+    // refractions on the other one
+    let wi = frame.to_local(direction.unit_vec());
+    let reflected = direction.dot(microfacet_normal) > 0.0;
+    if (wi.z() > 0.0) != reflected {
+        return None;
+    }
+
+    let shadowing = distribution.g2(wo, wi) / distribution.g1(wo);
+    Some(ScatterRecord {
+        attenuation: Color::with_values(shadowing, shadowing, shadowing),
+        scattered: Ray::with_time(rec.offset_origin(direction), direction, ray_in.time),
+        specular: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dielectric, Dispersion};
+    use crate::color::Color;
+    use crate::libcore::hit::HitRecord;
+    use crate::libcore::material::Material;
+    use crate::libcore::sampler::{IndependentSampler, Sampler};
+    use crate::math::{Point3, Ray, Vec3};
+
+    /// Hit at the origin by a ray going down, the normal facing it being +z
+    fn hit(material: &Dielectric, front_face: bool) -> HitRecord<'_> {
+        let outward = match front_face {
+            true => Vec3::with_values(0.0, 0.0, 1.0),
+            false => Vec3::with_values(0.0, 0.0, -1.0),
+        };
+        let ray = Ray::new(
+            Point3::with_values(0.0, 0.0, 1.0),
+            Vec3::with_values(0.0, 0.0, -1.0),
+        );
+        HitRecord::new_hit(Point3::new(), 1.0, &ray, &outward, material)
+    }
+
+    #[test]
+    fn frosted_glass_splits_reflection_and_refraction() {
+        let glass = Dielectric::new(1.5).with_roughness(0.4);
+        let ray = Ray::new(
+            Point3::with_values(0.0, -1.0, 1.0),
+            Vec3::with_values(0.0, 1.0, -1.0),
+        );
+        let rec = hit(&glass, true);
+        let mut sampler = IndependentSampler::new(0);
+        let (mut reflected, mut refracted) = (0, 0);
+        for i in 0..1000 {
+            sampler.start_pixel_sample((0, 0), i);
+            if let Some(s) = glass.scatter(&ray, &rec, &mut sampler) {
+                assert!(s.attenuation.x() > 0.0 && s.attenuation.x() <= 1.0);
+                match s.scattered.direction.z() > 0.0 {
+                    true => reflected += 1,
+                    false => refracted += 1,
+                }
+            }
+        }
+        // Fresnel reflectance of glass at 45 degrees is about 5%
+        assert!(reflected > 10 && reflected < 150, "{}", reflected);
+        assert!(refracted > 800, "{}", refracted);
+    }
+
+    #[test]
+    fn absorption_follows_the_interior_path_length() {
+        let glass = Dielectric::new(1.5).with_transmittance(Color::with_values(0.5, 0.8, 1.0), 2.0);
+        let ray = Ray::new(
+            Point3::with_values(0.0, 0.0, -4.0),
+            Vec3::with_values(0.0, 0.0, 2.0),
+        );
+        let mut rec = hit(&glass, false);
+        rec.t = 2.0;
+        let mut sampler = IndependentSampler::new(0);
+        sampler.start_pixel_sample((0, 0), 0);
+        // 4 units through the glass, twice the reference distance
+        let s = glass.scatter(&ray, &rec, &mut sampler).unwrap();
+        assert!((s.attenuation.x() - 0.25).abs() < 1e-9);
+        assert!((s.attenuation.y() - 0.64).abs() < 1e-9);
+        assert!((s.attenuation.z() - 1.0).abs() < 1e-9);
+        // Nothing is absorbed before entering
+        let rec = hit(&glass, true);
+        let s = glass.scatter(&ray, &rec, &mut sampler).unwrap();
+        assert!((s.attenuation.x() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn dispersion_follows_glass_catalogs() {
+        // Refractive indices of BK7 at the F, d and C lines
+        let bk7 = Dispersion::BK7;
+        assert!((bk7.ior(486.1) - 1.5224).abs() < 1e-3);
+        assert!((bk7.ior(587.6) - 1.5168).abs() < 1e-3);
+        assert!((bk7.ior(656.3) - 1.5143).abs() < 1e-3);
+        assert!((Dispersion::DIAMOND.ior(587.6) - 2.417).abs() < 2e-3);
+
+        let glass = Dielectric::new(1.0).with_dispersion(bk7);
+        let ray = Ray::new(
+            Point3::with_values(0.0, -1.0, 1.0),
+            Vec3::with_values(0.0, 1.0, -1.0),
+        );
+        let rec = hit(&glass, true);
+        let mut sampler = IndependentSampler::new(0);
+        sampler.start_pixel_sample((0, 0), 0);
+        // Only spectral rays are tied to their wavelength
+        let s = glass.scatter(&ray, &rec, &mut sampler).unwrap();
+        assert!(s.scattered.wavelength.is_none());
+        let ray = ray.with_wavelength(Some(450.0));
+        let s = glass.scatter(&ray, &rec, &mut sampler).unwrap();
+        assert_eq!(s.scattered.wavelength, Some(450.0));
+    }
+
+    #[test]
+    fn soap_bubbles_reflect_colors() {
+        let bubble = Dielectric::new(1.0).with_thin_film(350.0, 1.33);
+        let ray = Ray::new(
+            Point3::with_values(0.0, 0.0, 1.0),
+            Vec3::with_values(0.0, 0.0, -1.0),
+        );
+        let rec = hit(&bubble, true);
+        let mut sampler = IndependentSampler::new(0);
+        let (mut reflected, mut transmitted) = (Color::new(), Color::new());
+        let n = 4000;
+        for i in 0..n {
+            sampler.start_pixel_sample((0, 0), i);
+            let s = bubble.scatter(&ray, &rec, &mut sampler).unwrap();
+            match s.scattered.direction.z() > 0.0 {
+                true => reflected = reflected + s.attenuation / n as f64,
+                false => transmitted = transmitted + s.attenuation / n as f64,
+            }
+        }
+        // Energy is conserved and the reflection is tinted
+        let total = reflected + transmitted;
+        for c in 0..3 {
+            assert!((total[c] - 1.0).abs() < 0.05, "{:?}", total);
+        }
+        let spread = reflected.x().max(reflected.y()).max(reflected.z())
+            - reflected.x().min(reflected.y()).min(reflected.z());
+        assert!(spread > 0.02, "{:?}", reflected);
+    }
+
+    #[test]
+    fn frosted_glass_reflects_beyond_the_critical_angle() {
+        let glass = Dielectric::new(1.5).with_roughness(0.1);
+        // From inside the glass, well past the 42 degrees critical angle
+        let ray = Ray::new(
+            Point3::with_values(0.0, -1.0, 0.2),
+            Vec3::with_values(0.0, 1.0, -0.2),
+        );
+        let rec = hit(&glass, false);
+        let mut sampler = IndependentSampler::new(0);
+        for i in 0..200 {
+            sampler.start_pixel_sample((0, 0), i);
+            if let Some(s) = glass.scatter(&ray, &rec, &mut sampler) {
+                assert!(s.scattered.direction.z() > 0.0);
+            }
+        }
     }
 }
@@ -0,0 +1,26 @@
+use super::Material;
+use super::ScatterRecord;
+use crate::color::Color;
+use crate::libcore::hit::HitRecord;
+use crate::libcore::sampler::Sampler;
+use crate::math::Ray;
+
+pub struct DiffuseLight {
+    emit: Color<f64>,
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _: &Ray, _: &HitRecord, _: &mut dyn Sampler) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self) -> Color<f64> {
+        self.emit
+    }
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color<f64>) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
@@ -2,20 +2,36 @@ use super::Material;
 use super::ScatterRecord;
 use crate::color::Color;
 use crate::libcore::hit::HitRecord;
-use crate::math::{random_unit_vector, Ray, Vec3};
+use crate::libcore::sampler::Sampler;
+use crate::math::{sample_unit_vector, Ray, Vec3};
+use std::f64::consts::PI;
 
 pub struct Lambertian {
     albedo: Color<f64>,
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
-        let scatter_dir = rec.normal + random_unit_vector();
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let scatter_dir = rec.flip_above(rec.normal + sample_unit_vector(sampler.get_2d()));
         Some(ScatterRecord {
             attenuation: self.albedo,
-            scattered: Ray::new(rec.p, scatter_dir),
+            scattered: Ray::with_time(rec.offset_origin(scatter_dir), scatter_dir, ray_in.time),
+            specular: false,
         })
     }
+
+    fn eval(&self, rec: &HitRecord, _: Vec3<f64>, wi: Vec3<f64>) -> Color<f64> {
+        self.albedo * (f64::max(rec.normal.dot(wi), 0.0) / PI)
+    }
+
+    fn pdf(&self, rec: &HitRecord, _: Vec3<f64>, wi: Vec3<f64>) -> f64 {
+        f64::max(rec.normal.dot(wi), 0.0) / PI
+    }
 }
 
 impl Lambertian {
@@ -23,3 +39,42 @@ impl Lambertian {
         Lambertian { albedo }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Lambertian;
+    use crate::color::Color;
+    use crate::libcore::hit::HitRecord;
+    use crate::libcore::material::Material;
+    use crate::libcore::sampler::{IndependentSampler, Sampler};
+    use crate::math::{Point3, Ray, Vec3};
+
+    #[test]
+    fn tilted_normals_keep_the_albedo() {
+        let albedo = Color::with_values(0.8, 0.5, 0.2);
+        let material = Lambertian::new(albedo);
+        let ray = Ray::new(
+            Point3::with_values(0.0, 0.0, 1.0),
+            Vec3::with_values(0.0, 0.0, -1.0),
+        );
+        let up = Vec3::with_values(0.0, 0.0, 1.0);
+        let rec = HitRecord {
+            // Shading normal 80° off the geometric one
+            normal: Vec3::with_values(80f64.to_radians().sin(), 0.0, 80f64.to_radians().cos()),
+            ..HitRecord::new_hit(Point3::new(), 1.0, &ray, &up, &material)
+        };
+
+        let mut sampler = IndependentSampler::new(0);
+        let n = 1000;
+        let mut sum = Color::new();
+        for i in 0..n {
+            sampler.start_pixel_sample((0, 0), i);
+            if let Some(scatter) = material.scatter(&ray, &rec, &mut sampler) {
+                assert!(scatter.scattered.direction.dot(up) >= 0.0);
+                assert!(scatter.scattered.origin.z() > 0.0);
+                sum += &scatter.attenuation;
+            }
+        }
+        assert!((sum / n as f64 - albedo).length() < 1e-9);
+    }
+}
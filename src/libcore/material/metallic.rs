@@ -2,7 +2,8 @@ use super::Material;
 use super::ScatterRecord;
 use crate::color::Color;
 use crate::libcore::hit::HitRecord;
-use crate::math::{random_in_unit_sphere, reflect, Ray, Vec3};
+use crate::libcore::sampler::Sampler;
+use crate::math::{reflect, sample_in_unit_sphere, Ray, Vec3};
 
 pub struct Metallic {
     albedo: Color<f64>,
@@ -10,16 +11,20 @@ pub struct Metallic {
 }
 
 impl Material for Metallic {
-    fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
         let reflected = reflect(&ray_in.direction.unit_vec(), &rec.normal);
-        if reflected.dot(rec.normal) > 0.0 {
-            return Some(ScatterRecord {
-                attenuation: self.albedo,
-                scattered: Ray::new(rec.p, reflected + random_in_unit_sphere() * self.fuzz),
-            });
-        } else {
-            None
-        }
+        let fuzz = sample_in_unit_sphere(sampler.get_2d(), sampler.get_1d()) * self.fuzz;
+        let direction = rec.flip_above(reflected + fuzz);
+        Some(ScatterRecord {
+            attenuation: self.albedo,
+            scattered: Ray::with_time(rec.offset_origin(direction), direction, ray_in.time),
+            specular: true,
+        })
     }
 }
 
@@ -28,3 +33,42 @@ impl Metallic {
         Metallic { albedo, fuzz }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Metallic;
+    use crate::color::Color;
+    use crate::libcore::hit::HitRecord;
+    use crate::libcore::material::Material;
+    use crate::libcore::sampler::{IndependentSampler, Sampler};
+    use crate::math::{Point3, Ray, Vec3};
+
+    #[test]
+    fn tilted_normals_keep_the_albedo() {
+        let albedo = Color::with_values(0.9, 0.6, 0.3);
+        let material = Metallic::new(albedo, 0.5);
+        // Grazing ray, reflected into the surface by the tilted normal
+        let ray = Ray::new(
+            Point3::with_values(-1.0, 0.0, 0.2),
+            Vec3::with_values(1.0, 0.0, -0.2),
+        );
+        let up = Vec3::with_values(0.0, 0.0, 1.0);
+        let rec = HitRecord {
+            normal: Vec3::with_values(0.5f64, 0.0, 1.0).unit_vec(),
+            ..HitRecord::new_hit(Point3::new(), 1.0, &ray, &up, &material)
+        };
+
+        let mut sampler = IndependentSampler::new(0);
+        let n = 1000;
+        let mut sum = Color::new();
+        for i in 0..n {
+            sampler.start_pixel_sample((0, 0), i);
+            if let Some(scatter) = material.scatter(&ray, &rec, &mut sampler) {
+                assert!(scatter.scattered.direction.dot(up) >= 0.0);
+                assert!(scatter.scattered.origin.z() > 0.0);
+                sum += &scatter.attenuation;
+            }
+        }
+        assert!((sum / n as f64 - albedo).length() < 1e-9);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::math::Vec3;
+use std::f64::consts::PI;
+
+/// GGX / Trowbridge-Reitz distribution of microfacet normals, in the
+/// local shading frame where the macro normal is +z
+#[derive(Clone, Copy, Debug)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        TrowbridgeReitz {
+            alpha_x: f64::max(alpha_x, 1e-4),
+            alpha_y: f64::max(alpha_y, 1e-4),
+        }
+    }
+
+    /// Perceptual `roughness` in [0, 1], squared into the distribution
+    /// width. `anisotropy` in [0, 1] stretches the highlight along the
+    /// tangent.
+    pub fn from_roughness(roughness: f64, anisotropy: f64) -> Self {
+        let alpha = roughness * roughness;
+        let aspect = (1.0 - 0.9 * anisotropy).sqrt();
+        TrowbridgeReitz::new(alpha / aspect, alpha * aspect)
+    }
+
+    /// Density of microfacets facing `wm`
+    pub fn d(&self, wm: Vec3<f64>) -> f64 {
+        let cos2 = wm.z() * wm.z();
+        if cos2 <= 0.0 {
+            return 0.0;
+        }
+        let e = (wm.x() * wm.x() / (self.alpha_x * self.alpha_x)
+            + wm.y() * wm.y() / (self.alpha_y * self.alpha_y))
+            / cos2;
+        1.0 / (PI * self.alpha_x * self.alpha_y * cos2 * cos2 * (1.0 + e) * (1.0 + e))
+    }
+
+    /// Smith auxiliary function, the ratio of hidden to visible
+    /// microfacet area seen from `w`
+    fn lambda(&self, w: Vec3<f64>) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 <= 0.0 {
+            return f64::INFINITY;
+        }
+        let alpha2_tan2 = (self.alpha_x * self.alpha_x * w.x() * w.x()
+            + self.alpha_y * self.alpha_y * w.y() * w.y())
+            / cos2;
+        ((1.0 + alpha2_tan2).sqrt() - 1.0) / 2.0
+    }
+
+    /// Masking of the microsurface seen from `w`
+    pub fn g1(&self, w: Vec3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Height-correlated masking-shadowing between `wo` and `wi`
+    pub fn g2(&self, wo: Vec3<f64>, wi: Vec3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Microfacet normal visible from `wo` (Heitz 2018), drawn in
+    /// proportion to its projected area
+    pub fn sample_visible_normal(&self, wo: Vec3<f64>, u: (f64, f64)) -> Vec3<f64> {
+        // Stretch to the hemisphere configuration
+        let vh =
+            Vec3::with_values(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()).unit_vec();
+        let lensq = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = match lensq > 0.0 {
+            true => Vec3::with_values(-vh.y(), vh.x(), 0.0) / lensq.sqrt(),
+            false => Vec3::with_values(1.0, 0.0, 0.0),
+        };
+        let t2 = vh.cross(&t1);
+
+        // Disk sample warped towards the visible half
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = t1 * p1 + t2 * p2 + vh * f64::max(0.0, 1.0 - p1 * p1 - p2 * p2).sqrt();
+
+        // Back to the ellipsoid configuration
+        Vec3::with_values(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            f64::max(1e-6, nh.z()),
+        )
+        .unit_vec()
+    }
+
+    /// Density of `sample_visible_normal` returning `wm`
+    pub fn visible_normal_pdf(&self, wo: Vec3<f64>, wm: Vec3<f64>) -> f64 {
+        self.g1(wo) / wo.z().abs() * self.d(wm) * wo.dot(wm).abs()
+    }
+}
+
+/// Fresnel reflectance of a conductor of complex index of refraction
+/// `eta + i k` under the incidence cosine `cos_i`, unpolarized light
+pub fn fresnel_conductor(cos_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_i * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    (rp + rs) / 2.0
+}
+
+/// Fresnel reflectance of a dielectric interface under the incidence
+/// cosine `cos_i`, `eta` being the index of the far side over the one
+/// of the near side, 1 on total internal reflection
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fresnel_conductor, TrowbridgeReitz};
+    use crate::math::Vec3;
+
+    #[test]
+    fn conductor_fresnel_at_normal_incidence() {
+        let (eta, k) = (0.2, 3.9);
+        let expected = ((eta - 1.0) * (eta - 1.0) + k * k) / ((eta + 1.0) * (eta + 1.0) + k * k);
+        assert!((fresnel_conductor(1.0, eta, k) - expected).abs() < 1e-9);
+        // Grazing angles reflect everything
+        assert!(fresnel_conductor(1e-6, eta, k) > 0.999);
+    }
+
+    #[test]
+    fn visible_normals_face_the_viewer() {
+        let distribution = TrowbridgeReitz::from_roughness(0.6, 0.5);
+        let wo = Vec3::with_values(0.6f64, -0.3, 0.5).unit_vec();
+        let mut weight = 0.0;
+        let n = 64;
+        for i in 0..n {
+            for j in 0..n {
+                let u = ((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64);
+                let wm = distribution.sample_visible_normal(wo, u);
+                assert!(wm.z() > 0.0 && wo.dot(wm) >= -1e-9, "{:?}", wm);
+                assert!((wm.length() - 1.0).abs() < 1e-9);
+                // Shadowing weight of the reflected direction
+                let wi = wm * (2.0 * wo.dot(wm)) - wo;
+                if wi.z() > 0.0 {
+                    let w = distribution.g2(wo, wi) / distribution.g1(wo);
+                    assert!(w <= 1.0 + 1e-9);
+                    weight += w;
+                }
+            }
+        }
+        // Matches the integral of D G2 / (4 cos_o) over the hemisphere,
+        // the rest of the energy being lost to multiple scattering
+        let albedo = weight / (n * n) as f64;
+        assert!((albedo - 0.774).abs() < 0.01, "{}", albedo);
+    }
+}
@@ -1,20 +1,63 @@
 use crate::color::Color;
 use crate::libcore::hit::HitRecord;
+use crate::libcore::sampler::Sampler;
 use crate::math::ray::Ray;
+use crate::math::Vec3;
 
+mod bump;
+mod conductor;
 mod dielectric;
+mod diffuse_light;
 mod lambertian;
 mod metallic;
+mod microfacet;
+mod phase;
+mod principled;
+mod subsurface;
+mod thin_film;
 
-pub use dielectric::Dielectric;
+pub use bump::{BumpMapped, NormalMapped};
+pub use conductor::Conductor;
+pub use dielectric::{Dielectric, Dispersion};
+pub use diffuse_light::DiffuseLight;
 pub use lambertian::Lambertian;
 pub use metallic::Metallic;
+pub use microfacet::{fresnel_conductor, fresnel_dielectric, TrowbridgeReitz};
+pub use phase::{HenyeyGreenstein, Isotropic};
+pub use principled::Principled;
+pub use subsurface::Subsurface;
+pub use thin_film::ThinFilm;
 
 pub struct ScatterRecord {
     pub attenuation: Color<f64>,
     pub scattered: Ray,
+    /// Whether the scattered ray follows a specular (delta-like) lobe,
+    /// used to split the diffuse and specular render passes
+    pub specular: bool,
 }
 
 pub trait Material {
-    fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<ScatterRecord>;
+    fn scatter(
+        &self,
+        ray: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord>;
+
+    fn emitted(&self) -> Color<f64> {
+        Color::new()
+    }
+
+    /// BSDF times the cosine of `wi` between the unit directions `wo`
+    /// towards the viewer and `wi` towards the light, for integrators
+    /// sampling lights. Black for specular materials.
+    fn eval(&self, _rec: &HitRecord, _wo: Vec3<f64>, _wi: Vec3<f64>) -> Color<f64> {
+        Color::new()
+    }
+
+    /// Density of `scatter` picking the direction `wi`
+    /// from `wo`, 0 for specular materials
+    fn pdf(&self, _rec: &HitRecord, _wo: Vec3<f64>, _wi: Vec3<f64>) -> f64 {
+        0.0
+    }
 }
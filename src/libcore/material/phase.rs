@@ -0,0 +1,139 @@
+use super::Material;
+use super::ScatterRecord;
+use crate::color::Color;
+use crate::libcore::hit::HitRecord;
+use crate::libcore::sampler::Sampler;
+use crate::math::{clamp, sample_unit_vector, Onb, Ray, Vec3};
+use std::f64::consts::PI;
+
+/// Phase function scattering light evenly in every direction,
+/// for participating media
+pub struct Isotropic {
+    albedo: Color<f64>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color<f64>) -> Isotropic {
+        Isotropic { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.albedo,
+            scattered: Ray::with_time(rec.p, sample_unit_vector(sampler.get_2d()), ray_in.time),
+            specular: false,
+        })
+    }
+
+    /// Phase function value, media having no cosine term
+    fn eval(&self, _: &HitRecord, _: Vec3<f64>, _: Vec3<f64>) -> Color<f64> {
+        self.albedo / (4.0 * PI)
+    }
+
+    fn pdf(&self, _: &HitRecord, _: Vec3<f64>, _: Vec3<f64>) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+/// Henyey-Greenstein phase function, `g` in (-1, 1) being the mean
+/// cosine of the scattering angle: positive values scatter forward
+/// like haze and smoke, negative ones back towards the light
+pub struct HenyeyGreenstein {
+    albedo: Color<f64>,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Color<f64>, g: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein {
+            albedo,
+            g: clamp(g, -0.999, 0.999),
+        }
+    }
+
+    /// Density of turning by an angle of cosine `cos_theta`
+    fn phase(&self, cos_theta: f64) -> f64 {
+        let denom = 1.0 + self.g * self.g - 2.0 * self.g * cos_theta;
+        (1.0 - self.g * self.g) / (4.0 * PI * denom * denom.sqrt())
+    }
+
+    /// Cosine of the scattering angle for the uniform sample `u`
+    fn sample_cos_theta(&self, u: f64) -> f64 {
+        let g = self.g;
+        if g.abs() < 1e-3 {
+            return 1.0 - 2.0 * u;
+        }
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+        (1.0 + g * g - s * s) / (2.0 * g)
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let (u0, u1) = sampler.get_2d();
+        let cos_theta = self.sample_cos_theta(u0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u1;
+        let frame = Onb::from_w(ray_in.direction.unit_vec());
+        let direction = frame.local(Vec3::with_values(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ));
+        Some(ScatterRecord {
+            attenuation: self.albedo,
+            scattered: Ray::with_time(rec.p, direction, ray_in.time),
+            specular: false,
+        })
+    }
+
+    /// Phase function value between the propagation directions,
+    /// `wo` pointing back towards the viewer
+    fn eval(&self, _: &HitRecord, wo: Vec3<f64>, wi: Vec3<f64>) -> Color<f64> {
+        self.albedo * self.phase(-wo.dot(wi))
+    }
+
+    fn pdf(&self, _: &HitRecord, wo: Vec3<f64>, wi: Vec3<f64>) -> f64 {
+        self.phase(-wo.dot(wi))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HenyeyGreenstein;
+    use crate::color::Color;
+
+    #[test]
+    fn mean_cosine_is_the_anisotropy() {
+        for g in [-0.6, 0.0, 0.3, 0.9].iter() {
+            let phase = HenyeyGreenstein::new(Color::new(), *g);
+            let n = 100000;
+            let mean: f64 = (0..n)
+                .map(|i| phase.sample_cos_theta((i as f64 + 0.5) / n as f64))
+                .sum::<f64>()
+                / n as f64;
+            assert!((mean - g).abs() < 1e-3, "{} {}", g, mean);
+
+            // The density integrates to one over the sphere
+            let integral: f64 = (0..n)
+                .map(|i| {
+                    let cos = -1.0 + 2.0 * (i as f64 + 0.5) / n as f64;
+                    phase.phase(cos) * 2.0 * std::f64::consts::PI * 2.0 / n as f64
+                })
+                .sum();
+            assert!((integral - 1.0).abs() < 1e-3, "{}", integral);
+        }
+    }
+}
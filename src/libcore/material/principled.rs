@@ -0,0 +1,502 @@
+use super::microfacet::{fresnel_dielectric, TrowbridgeReitz};
+use super::Material;
+use super::ScatterRecord;
+use crate::color::{luminance, Color};
+use crate::libcore::hit::HitRecord;
+use crate::libcore::sampler::Sampler;
+use crate::libcore::texture::Texture;
+use crate::math::{clamp, lerp, sample_unit_disk, Ray, Vec3};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+type TextureRef = Arc<dyn Texture + Send + Sync>;
+
+/// Disney principled BSDF (Burley 2012, 2015): one material blending
+/// a diffuse base with retro-reflection, flattened subsurface and
+/// sheen, a GGX specular layer, a clearcoat and rough glass. Every
+/// parameter is a texture, scalar ones reading its luminance.
+pub struct Principled {
+    base_color: TextureRef,
+    metallic: TextureRef,
+    roughness: TextureRef,
+    specular: TextureRef,
+    specular_tint: TextureRef,
+    sheen: TextureRef,
+    sheen_tint: TextureRef,
+    clearcoat: TextureRef,
+    clearcoat_gloss: TextureRef,
+    transmission: TextureRef,
+    subsurface: TextureRef,
+    ior: f64,
+}
+
+impl Principled {
+    /// Rough dielectric of the given color
+    pub fn new<T: Texture + Send + Sync + 'static>(base_color: T) -> Principled {
+        Principled {
+            base_color: Arc::new(base_color),
+            metallic: Arc::new(0.0),
+            roughness: Arc::new(0.5),
+            specular: Arc::new(0.5),
+            specular_tint: Arc::new(0.0),
+            sheen: Arc::new(0.0),
+            sheen_tint: Arc::new(0.5),
+            clearcoat: Arc::new(0.0),
+            clearcoat_gloss: Arc::new(1.0),
+            transmission: Arc::new(0.0),
+            subsurface: Arc::new(0.0),
+            ior: 1.5,
+        }
+    }
+
+    pub fn with_metallic<T: Texture + Send + Sync + 'static>(mut self, metallic: T) -> Self {
+        self.metallic = Arc::new(metallic);
+        self
+    }
+
+    pub fn with_roughness<T: Texture + Send + Sync + 'static>(mut self, roughness: T) -> Self {
+        self.roughness = Arc::new(roughness);
+        self
+    }
+
+    /// Normal incidence reflectance of dielectrics, 0.5 being 4%
+    pub fn with_specular<T: Texture + Send + Sync + 'static>(mut self, specular: T) -> Self {
+        self.specular = Arc::new(specular);
+        self
+    }
+
+    /// Tints dielectric reflections towards the base color
+    pub fn with_specular_tint<T: Texture + Send + Sync + 'static>(mut self, tint: T) -> Self {
+        self.specular_tint = Arc::new(tint);
+        self
+    }
+
+    /// Grazing retro-reflection of cloth
+    pub fn with_sheen<T: Texture + Send + Sync + 'static>(mut self, sheen: T) -> Self {
+        self.sheen = Arc::new(sheen);
+        self
+    }
+
+    pub fn with_sheen_tint<T: Texture + Send + Sync + 'static>(mut self, tint: T) -> Self {
+        self.sheen_tint = Arc::new(tint);
+        self
+    }
+
+    /// Second, white and glossier specular layer like car paint varnish
+    pub fn with_clearcoat<T: Texture + Send + Sync + 'static>(mut self, clearcoat: T) -> Self {
+        self.clearcoat = Arc::new(clearcoat);
+        self
+    }
+
+    pub fn with_clearcoat_gloss<T: Texture + Send + Sync + 'static>(mut self, gloss: T) -> Self {
+        self.clearcoat_gloss = Arc::new(gloss);
+        self
+    }
+
+    /// Part of the dielectric base made of glass
+    pub fn with_transmission<T: Texture + Send + Sync + 'static>(
+        mut self,
+        transmission: T,
+    ) -> Self {
+        self.transmission = Arc::new(transmission);
+        self
+    }
+
+    /// Flattens the diffuse base like light scattered under the surface
+    pub fn with_subsurface<T: Texture + Send + Sync + 'static>(mut self, subsurface: T) -> Self {
+        self.subsurface = Arc::new(subsurface);
+        self
+    }
+
+    /// Index of refraction of the glass part
+    pub fn with_ior(mut self, ior: f64) -> Self {
+        self.ior = ior;
+        self
+    }
+
+    fn lobes(&self, rec: &HitRecord) -> Lobes {
+        let color = |t: &TextureRef| t.value(rec.u, rec.v, &rec.p);
+        let scalar = |t: &TextureRef| clamp(luminance(&color(t)), 0.0, 1.0);
+
+        let base_color = color(&self.base_color);
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness);
+        let transmission = scalar(&self.transmission);
+        let clearcoat = scalar(&self.clearcoat);
+
+        let white = Color::with_values(1.0, 1.0, 1.0);
+        let tint = match luminance(&base_color) {
+            l if l > 0.0 => base_color / l,
+            _ => white,
+        };
+        let dielectric_specular =
+            lerp(&white, &tint, scalar(&self.specular_tint)) * (0.08 * scalar(&self.specular));
+
+        Lobes {
+            base_color,
+            roughness,
+            subsurface: scalar(&self.subsurface),
+            sheen: lerp(&white, &tint, scalar(&self.sheen_tint)) * scalar(&self.sheen),
+            specular: lerp(&dielectric_specular, &base_color, metallic),
+            clearcoat,
+            clearcoat_alpha: lerp(&0.1, &0.001, scalar(&self.clearcoat_gloss)),
+            eta: match rec.front_face {
+                true => self.ior,
+                false => 1.0 / self.ior,
+            },
+            distribution: TrowbridgeReitz::from_roughness(roughness, 0.0),
+            diffuse_weight: (1.0 - metallic) * (1.0 - transmission),
+            glass_weight: (1.0 - metallic) * transmission,
+            specular_weight: 1.0 - (1.0 - metallic) * transmission,
+            clearcoat_weight: 0.25 * clearcoat,
+        }
+    }
+}
+
+/// Parameters of a hit, in the shading frame where the normal is +z
+/// and `wo` towards the viewer is above the surface
+struct Lobes {
+    base_color: Color<f64>,
+    roughness: f64,
+    subsurface: f64,
+    sheen: Color<f64>,
+    /// Normal incidence reflectance of the specular layer
+    specular: Color<f64>,
+    clearcoat: f64,
+    clearcoat_alpha: f64,
+    /// Relative index of refraction of the far side
+    eta: f64,
+    distribution: TrowbridgeReitz,
+    diffuse_weight: f64,
+    glass_weight: f64,
+    specular_weight: f64,
+    clearcoat_weight: f64,
+}
+
+impl Lobes {
+    /// Probabilities to sample the diffuse, specular, glass and
+    /// clearcoat lobes
+    fn probabilities(&self) -> [f64; 4] {
+        let weights = [
+            self.diffuse_weight,
+            self.specular_weight,
+            self.glass_weight,
+            self.clearcoat_weight,
+        ];
+        let total: f64 = weights.iter().sum();
+        [
+            weights[0] / total,
+            weights[1] / total,
+            weights[2] / total,
+            weights[3] / total,
+        ]
+    }
+
+    /// BSDF times the cosine of `wi`
+    fn eval(&self, wo: Vec3<f64>, wi: Vec3<f64>) -> Color<f64> {
+        if wi.z() > 0.0 {
+            let h = (wo + wi).unit_vec();
+            let cos_d = wi.dot(h);
+            let fh = schlick_weight(cos_d);
+            let mut f = Color::new();
+
+            if self.diffuse_weight > 0.0 {
+                let (fl, fv) = (schlick_weight(wi.z()), schlick_weight(wo.z()));
+                let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+                let fd = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv);
+                let fss90 = self.roughness * cos_d * cos_d;
+                let fss = (1.0 + (fss90 - 1.0) * fl) * (1.0 + (fss90 - 1.0) * fv);
+                let ss = 1.25 * (fss * (1.0 / (wi.z() + wo.z()) - 0.5) + 0.5);
+                let diffuse = self.base_color * (lerp(&fd, &ss, self.subsurface) / PI);
+                f = f + (diffuse + self.sheen * fh) * self.diffuse_weight;
+            }
+
+            let microfacet =
+                self.distribution.d(h) * self.distribution.g2(wo, wi) / (4.0 * wo.z() * wi.z());
+            if self.specular_weight > 0.0 {
+                let white = Color::with_values(1.0, 1.0, 1.0);
+                let fresnel = lerp(&self.specular, &white, fh);
+                f = f + fresnel * (microfacet * self.specular_weight);
+            }
+            if self.glass_weight > 0.0 {
+                let fresnel = fresnel_dielectric(wo.dot(h), self.eta);
+                let r = fresnel * microfacet * self.glass_weight;
+                f = f + Color::with_values(r, r, r);
+            }
+            if self.clearcoat > 0.0 {
+                let fresnel = lerp(&0.04, &1.0, fh);
+                let r = 0.25
+                    * self.clearcoat
+                    * gtr1(h.z(), self.clearcoat_alpha)
+                    * fresnel
+                    * smith_visibility(wo.z(), 0.25)
+                    * smith_visibility(wi.z(), 0.25);
+                f = f + Color::with_values(r, r, r);
+            }
+            f * wi.z()
+        } else if wi.z() < 0.0 && self.glass_weight > 0.0 {
+            let (wm, denom) = match self.refraction_half_vector(wo, wi) {
+                Some(h) => h,
+                None => return Color::new(),
+            };
+            let fresnel = fresnel_dielectric(wo.dot(wm), self.eta);
+            let t = (1.0 - fresnel)
+                * self.distribution.d(wm)
+                * self.distribution.g2(wo, wi)
+                * (wi.dot(wm) * wo.dot(wm) / (wi.z() * wo.z() * denom)).abs();
+            self.base_color * (t * self.glass_weight * -wi.z())
+        } else {
+            Color::new()
+        }
+    }
+
+    /// Generalized half vector of a refraction, facing the normal, with
+    /// the squared denominator of the change of variables to it
+    fn refraction_half_vector(&self, wo: Vec3<f64>, wi: Vec3<f64>) -> Option<(Vec3<f64>, f64)> {
+        let mut wm = (wo + wi * self.eta).unit_vec();
+        if wm.z() < 0.0 {
+            wm = -wm;
+        }
+        if wo.dot(wm) <= 0.0 || wi.dot(wm) >= 0.0 {
+            return None;
+        }
+        let denom = wi.dot(wm) + wo.dot(wm) / self.eta;
+        Some((wm, denom * denom))
+    }
+
+    fn pdf(&self, wo: Vec3<f64>, wi: Vec3<f64>) -> f64 {
+        let [p_diffuse, p_specular, p_glass, p_clearcoat] = self.probabilities();
+        if wi.z() > 0.0 {
+            let h = (wo + wi).unit_vec();
+            if wo.dot(h) <= 0.0 {
+                return 0.0;
+            }
+            let reflection = self.distribution.visible_normal_pdf(wo, h) / (4.0 * wo.dot(h));
+            let glass = reflection * fresnel_dielectric(wo.dot(h), self.eta);
+            let clearcoat = gtr1(h.z(), self.clearcoat_alpha) * h.z() / (4.0 * wo.dot(h));
+            p_diffuse * wi.z() / PI
+                + p_specular * reflection
+                + p_glass * glass
+                + p_clearcoat * clearcoat
+        } else {
+            match self.refraction_half_vector(wo, wi) {
+                Some((wm, denom)) => {
+                    let fresnel = fresnel_dielectric(wo.dot(wm), self.eta);
+                    p_glass * self.distribution.visible_normal_pdf(wo, wm) * wi.dot(wm).abs()
+                        / denom
+                        * (1.0 - fresnel)
+                }
+                None => 0.0,
+            }
+        }
+    }
+
+    /// Direction drawn from one of the lobes, picked with `u_lobe`,
+    /// and whether the lobe is a specular one. None when a reflection
+    /// goes below the surface or a refraction above it.
+    fn sample(&self, wo: Vec3<f64>, u_lobe: f64, u: (f64, f64)) -> Option<(Vec3<f64>, bool)> {
+        let reflect = |wm: Vec3<f64>| wm * (2.0 * wo.dot(wm)) - wo;
+        let [p_diffuse, p_specular, p_glass, _] = self.probabilities();
+
+        if u_lobe < p_diffuse {
+            let d = sample_unit_disk(u);
+            let z = f64::max(0.0, 1.0 - d.x() * d.x() - d.y() * d.y()).sqrt();
+            return Some((Vec3::with_values(d.x(), d.y(), z), false));
+        }
+        let (wi, reflected) = if u_lobe < p_diffuse + p_specular {
+            (
+                reflect(self.distribution.sample_visible_normal(wo, u)),
+                true,
+            )
+        } else if u_lobe < p_diffuse + p_specular + p_glass {
+            let wm = self.distribution.sample_visible_normal(wo, u);
+            let cos_o = wo.dot(wm);
+            let fresnel = fresnel_dielectric(cos_o, self.eta);
+            // Reuses the part of the lobe sample inside the glass lobe
+            let u_fresnel = (u_lobe - p_diffuse - p_specular) / p_glass;
+            match u_fresnel < fresnel {
+                true => (reflect(wm), true),
+                false => {
+                    let sin2_t = (1.0 - cos_o * cos_o) / (self.eta * self.eta);
+                    let cos_t = (1.0 - sin2_t).sqrt();
+                    (-wo / self.eta + wm * (cos_o / self.eta - cos_t), false)
+                }
+            }
+        } else {
+            let a2 = self.clearcoat_alpha * self.clearcoat_alpha;
+            let cos_theta = f64::max(0.0, (1.0 - a2.powf(1.0 - u.0)) / (1.0 - a2)).sqrt();
+            let sin_theta = f64::max(0.0, 1.0 - cos_theta * cos_theta).sqrt();
+            let phi = 2.0 * PI * u.1;
+            let h = Vec3::with_values(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+            (reflect(h), true)
+        };
+        match (wi.z() > 0.0) == reflected {
+            true => Some((wi, true)),
+            false => None,
+        }
+    }
+}
+
+fn schlick_weight(cos: f64) -> f64 {
+    clamp(1.0 - cos, 0.0, 1.0).powi(5)
+}
+
+/// Berry distribution of the clearcoat
+fn gtr1(cos_h: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let t = 1.0 + (a2 - 1.0) * cos_h * cos_h;
+    (a2 - 1.0) / (PI * a2.ln() * t)
+}
+
+/// Smith GGX masking divided by twice the cosine
+fn smith_visibility(cos: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let c2 = cos * cos;
+    1.0 / (cos + (a2 + c2 - a2 * c2).sqrt())
+}
+
+impl Material for Principled {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let frame = rec.tangent_frame();
+        let wo = frame.to_local(-ray_in.direction.unit_vec());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let lobes = self.lobes(rec);
+        let (wi, specular) = lobes.sample(wo, sampler.get_1d(), sampler.get_2d())?;
+
+        // Weighted by the density of all the lobes together
+        let pdf = lobes.pdf(wo, wi);
+        if pdf <= 0.0 || !pdf.is_finite() {
+            return None;
+        }
+        Some(ScatterRecord {
+            attenuation: lobes.eval(wo, wi) / pdf,
+            scattered: Ray::with_time(rec.p, frame.local(wi), ray_in.time),
+            specular,
+        })
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: Vec3<f64>, wi: Vec3<f64>) -> Color<f64> {
+        let frame = rec.tangent_frame();
+        let wo = frame.to_local(wo);
+        match wo.z() > 0.0 {
+            true => self.lobes(rec).eval(wo, frame.to_local(wi)),
+            false => Color::new(),
+        }
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: Vec3<f64>, wi: Vec3<f64>) -> f64 {
+        let frame = rec.tangent_frame();
+        let wo = frame.to_local(wo);
+        match wo.z() > 0.0 {
+            true => self.lobes(rec).pdf(wo, frame.to_local(wi)),
+            false => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Principled;
+    use crate::color::Color;
+    use crate::libcore::hit::HitRecord;
+    use crate::libcore::material::Material;
+    use crate::libcore::sampler::{IndependentSampler, Sampler};
+    use crate::math::{Point3, Ray, Vec3};
+    use std::f64::consts::PI;
+
+    /// Hit at the origin by a ray going down, the normal being +z
+    fn hit(material: &Principled) -> HitRecord<'_> {
+        let ray = Ray::new(
+            Point3::with_values(0.0, 0.0, 1.0),
+            Vec3::with_values(0.0, 0.0, -1.0),
+        );
+        HitRecord::new_hit(
+            Point3::new(),
+            1.0,
+            &ray,
+            &Vec3::with_values(0.0, 0.0, 1.0),
+            material,
+        )
+    }
+
+    /// The mean sampling weight matches the integral of the BSDF
+    /// computed by quadrature, which only holds when the directions
+    /// follow the pdf
+    #[test]
+    fn sampling_matches_the_pdf() {
+        let material = Principled::new(Color::with_values(0.8, 0.5, 0.2))
+            .with_roughness(0.5)
+            .with_sheen(0.5)
+            .with_clearcoat(0.5)
+            .with_clearcoat_gloss(0.3)
+            .with_transmission(0.4);
+        let rec = hit(&material);
+        let wo = Vec3::with_values(0.5f64, 0.2, 0.8).unit_vec();
+
+        let n = 600;
+        let (mut integral, mut pdf_integral) = (Color::new(), 0.0);
+        for i in 0..n {
+            for j in 0..2 * n {
+                let cos_theta = 1.0 - 2.0 * (i as f64 + 0.5) / n as f64;
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let phi = PI * (j as f64 + 0.5) / n as f64;
+                let wi = Vec3::with_values(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                let solid_angle = 4.0 * PI / (2 * n * n) as f64;
+                integral = integral + material.eval(&rec, wo, wi) * solid_angle;
+                pdf_integral += material.pdf(&rec, wo, wi) * solid_angle;
+            }
+        }
+        // Some reflections are lost below the surface
+        assert!(
+            pdf_integral > 0.9 && pdf_integral < 1.0 + 1e-3,
+            "{}",
+            pdf_integral
+        );
+
+        let ray = Ray::new(Point3::new() + wo, -wo);
+        let mut sampler = IndependentSampler::new(0);
+        let mut estimate = Color::new();
+        let samples = 200000;
+        for i in 0..samples {
+            sampler.start_pixel_sample((0, 0), i);
+            if let Some(s) = material.scatter(&ray, &rec, &mut sampler) {
+                estimate = estimate + s.attenuation / samples as f64;
+            }
+        }
+        for c in 0..3 {
+            assert!(
+                (estimate[c] - integral[c]).abs() < 0.02,
+                "{:?} {:?}",
+                estimate,
+                integral
+            );
+        }
+    }
+
+    #[test]
+    fn white_metal_keeps_at_most_all_the_energy() {
+        let material = Principled::new(Color::with_values(1.0, 1.0, 1.0))
+            .with_metallic(1.0)
+            .with_roughness(0.3);
+        let rec = hit(&material);
+        let wo = Vec3::with_values(0.3f64, 0.0, 0.9).unit_vec();
+        let ray = Ray::new(Point3::new() + wo, -wo);
+        let mut sampler = IndependentSampler::new(1);
+        let mut albedo = 0.0;
+        for i in 0..10000 {
+            sampler.start_pixel_sample((0, 0), i);
+            if let Some(s) = material.scatter(&ray, &rec, &mut sampler) {
+                assert!(s.scattered.direction.z() > 0.0);
+                albedo += s.attenuation.x() / 10000.0;
+            }
+        }
+        assert!(albedo > 0.85 && albedo <= 1.0, "{}", albedo);
+    }
+}
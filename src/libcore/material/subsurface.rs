@@ -0,0 +1,151 @@
+use super::Material;
+use super::ScatterRecord;
+use crate::color::Color;
+use crate::libcore::hit::HitRecord;
+use crate::libcore::sampler::Sampler;
+use crate::math::{clamp, reflect, refract, sample_unit_vector, schlick, Ray};
+
+/// Translucent material like skin, wax or marble: light refracts into
+/// the object and random walks through its volume (Chiang et al. 2016)
+/// until it leaves through the surface. Every step inside is a bounce
+/// of the path, so it needs closed objects and a generous depth.
+pub struct Subsurface {
+    /// Single scattering albedo
+    albedo: Color<f64>,
+    /// Extinction coefficient, the inverse of the mean free path
+    extinction: f64,
+    ior: f64,
+}
+
+impl Subsurface {
+    /// `color` is the overall color of the object once light has
+    /// scattered many times inside, `mean_free_path` the average
+    /// distance between two scattering events in scene units
+    pub fn new(color: Color<f64>, mean_free_path: f64) -> Subsurface {
+        // Inversion of the multiple scattering albedo (van de Hulst)
+        let albedo = |a: f64| {
+            let a = clamp(a, 0.0, 1.0);
+            let s = 4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt();
+            1.0 - s * s
+        };
+        Subsurface {
+            albedo: Color::with_values(albedo(color.x()), albedo(color.y()), albedo(color.z())),
+            extinction: 1.0 / mean_free_path.max(1e-6),
+            ior: 1.4,
+        }
+    }
+
+    pub fn with_ior(mut self, ior: f64) -> Self {
+        self.ior = ior;
+        self
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let unit_direction = ray_in.direction.unit_vec();
+
+        if !rec.front_face {
+            // Free flight inside, scattering before reaching the surface
+            let distance = -(1.0 - sampler.get_1d()).ln() / self.extinction;
+            if distance < rec.t * ray_in.direction.length() {
+                // Away from the surface, no offset needed
+                let origin = ray_in.origin + unit_direction * distance;
+                let direction = sample_unit_vector(sampler.get_2d());
+                return Some(ScatterRecord {
+                    attenuation: self.albedo,
+                    scattered: Ray::with_time(origin, direction, ray_in.time),
+                    specular: false,
+                });
+            }
+        }
+
+        // Smooth interface, light bouncing back inside being reflected
+        let etai_over_eta = match rec.front_face {
+            true => 1.0 / self.ior,
+            false => self.ior,
+        };
+        let cos_theta = f64::min(1.0, rec.normal.dot(-unit_direction));
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let direction = match etai_over_eta * sin_theta > 1.0
+            || sampler.get_1d() < schlick(cos_theta, etai_over_eta)
+        {
+            true => reflect(&unit_direction, &rec.normal),
+            false => refract(&unit_direction, &rec.normal, etai_over_eta),
+        };
+        Some(ScatterRecord {
+            attenuation: Color::with_values(1.0, 1.0, 1.0),
+            scattered: Ray::with_time(rec.offset_origin(direction), direction, ray_in.time),
+            specular: rec.front_face,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Subsurface;
+    use crate::color::Color;
+    use crate::libcore::hit::Hittable;
+    use crate::libcore::sampler::{IndependentSampler, Sampler};
+    use crate::math::sphere::Sphere;
+    use crate::math::{Point3, Ray, Vec3};
+    use std::sync::Arc;
+
+    /// Mean weight of the paths leaving a sphere of the material
+    fn walk(color: Color<f64>) -> Color<f64> {
+        let material = Subsurface::new(color, 0.1);
+        let sphere = Sphere::new(Point3::new(), 1.0, Arc::new(material));
+        let mut sampler = IndependentSampler::new(0);
+        let n = 2000;
+        let mut total = Color::new();
+        for i in 0..n {
+            sampler.start_pixel_sample((0, 0), i);
+            let mut ray = Ray::new(
+                Point3::with_values(0.0, 0.0, 3.0),
+                Vec3::with_values(0.0, 0.0, -1.0),
+            );
+            let mut throughput = Color::with_values(1.0, 1.0, 1.0);
+            for _ in 0..10000 {
+                let rec = match sphere.hit(&ray, 0.001, f64::MAX) {
+                    Some(rec) => rec,
+                    None => {
+                        total = total + throughput / n as f64;
+                        break;
+                    }
+                };
+                match rec.material.scatter(&ray, &rec, &mut sampler) {
+                    Some(s) => {
+                        throughput = throughput * s.attenuation;
+                        ray = s.scattered;
+                    }
+                    None => break,
+                }
+            }
+        }
+        total
+    }
+
+    #[test]
+    fn white_media_do_not_absorb() {
+        let total = walk(Color::with_values(1.0, 1.0, 1.0));
+        for c in 0..3 {
+            assert!((total[c] - 1.0).abs() < 0.05, "{:?}", total);
+        }
+    }
+
+    #[test]
+    fn color_comes_from_absorption_inside() {
+        let total = walk(Color::with_values(0.8, 0.4, 0.2));
+        assert!(
+            total.x() > total.y() && total.y() > total.z(),
+            "{:?}",
+            total
+        );
+        assert!(total.x() < 1.0, "{:?}", total);
+    }
+}
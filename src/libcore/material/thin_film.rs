@@ -0,0 +1,104 @@
+use crate::color::spectrum::integrate_spectrum;
+use crate::color::Color;
+use crate::math::clamp;
+use num::complex::Complex64;
+use std::f64::consts::PI;
+
+/// Number of wavelengths integrated by RGB renders
+const RGB_SAMPLES: usize = 32;
+
+/// Thin transparent layer coating a surface, whose reflections
+/// interfere into angle dependent colors like soap bubbles, oil
+/// slicks or anodized metal
+#[derive(Clone, Copy, Debug)]
+pub struct ThinFilm {
+    /// Thickness in nanometers
+    pub thickness: f64,
+    pub ior: f64,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f64, ior: f64) -> Self {
+        ThinFilm { thickness, ior }
+    }
+
+    /// Reflectance at `wavelength` nanometers of the coated interface
+    /// (Airy summation), for unpolarized light coming from a medium of
+    /// index `eta_i` with the incidence cosine `cos_i`, onto a substrate
+    /// of complex index `eta_t`
+    pub fn reflectance(&self, cos_i: f64, eta_i: f64, eta_t: Complex64, wavelength: f64) -> f64 {
+        let n0 = Complex64::new(eta_i, 0.0);
+        let n1 = Complex64::new(self.ior, 0.0);
+        let n2 = eta_t;
+        let cos0 = Complex64::new(cos_i, 0.0);
+        // Snell's law, complex beyond the critical angle and in metals
+        let sin0 = Complex64::new((1.0 - cos_i * cos_i).max(0.0).sqrt(), 0.0);
+        let cosine = |n: Complex64| {
+            let s = n0 * sin0 / n;
+            (Complex64::new(1.0, 0.0) - s * s).sqrt()
+        };
+        let (cos1, cos2) = (cosine(n1), cosine(n2));
+
+        // Phase difference of one round trip inside the film
+        let delta = n1 * cos1 * (4.0 * PI * self.thickness / wavelength);
+        let phase = (Complex64::i() * delta).exp();
+        let airy = |r01: Complex64, r12: Complex64| {
+            let r = (r01 + r12 * phase) / (Complex64::new(1.0, 0.0) + r01 * r12 * phase);
+            r.norm_sqr()
+        };
+
+        let s = airy(
+            (n0 * cos0 - n1 * cos1) / (n0 * cos0 + n1 * cos1),
+            (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2),
+        );
+        let p = airy(
+            (n1 * cos0 - n0 * cos1) / (n1 * cos0 + n0 * cos1),
+            (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2),
+        );
+        f64::min((s + p) / 2.0, 1.0)
+    }
+
+    /// Color of the reflectance over the visible spectrum, `eta_t`
+    /// giving the substrate index at every wavelength
+    pub fn reflectance_rgb<F: Fn(f64) -> Complex64>(
+        &self,
+        cos_i: f64,
+        eta_i: f64,
+        eta_t: F,
+    ) -> Color<f64> {
+        let rgb = integrate_spectrum(
+            |lambda| self.reflectance(cos_i, eta_i, eta_t(lambda), lambda),
+            RGB_SAMPLES,
+        );
+        Color::with_values(
+            clamp(rgb.x(), 0.0, 1.0),
+            clamp(rgb.y(), 0.0, 1.0),
+            clamp(rgb.z(), 0.0, 1.0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ThinFilm;
+    use num::complex::Complex64;
+
+    #[test]
+    fn vanishing_films_leave_the_substrate_fresnel() {
+        let film = ThinFilm::new(0.0, 1.33);
+        let glass = Complex64::new(1.5, 0.0);
+        // 4% for glass at normal incidence
+        assert!((film.reflectance(1.0, 1.0, glass, 550.0) - 0.04).abs() < 1e-9);
+    }
+
+    #[test]
+    fn quarter_wave_coatings_cancel_reflections() {
+        // Ideal antireflection coating: index sqrt(1.5), quarter wave thick
+        let ior = 1.5f64.sqrt();
+        let film = ThinFilm::new(550.0 / (4.0 * ior), ior);
+        let glass = Complex64::new(1.5, 0.0);
+        assert!(film.reflectance(1.0, 1.0, glass, 550.0) < 1e-9);
+        // But not for every wavelength, giving the coating its tint
+        assert!(film.reflectance(1.0, 1.0, glass, 400.0) > 0.005);
+    }
+}
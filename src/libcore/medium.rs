@@ -0,0 +1,412 @@
+use super::aabb::Aabb;
+use super::hit::{HitRecord, Hittable};
+use super::material::Material;
+use super::sampler::Sampler;
+use super::volume::DensityGrid;
+use crate::math::{Point3, Ray, Vec3};
+use std::sync::Arc;
+
+/// Record of a scattering event inside a medium at distance `t`
+/// along the ray
+fn medium_hit<'a>(ray: &Ray, t: f64, phase: &'a dyn Material) -> HitRecord<'a> {
+    // Arbitrary, media having no surface
+    let normal = -ray.direction.unit_vec();
+    HitRecord {
+        p: ray.at(t),
+        normal,
+        geometric_normal: normal,
+        t,
+        front_face: true,
+        u: 0.0,
+        v: 0.0,
+        dpdu: Vec3::new(),
+        dpdv: Vec3::new(),
+        material: phase,
+        object_id: 0,
+    }
+}
+
+/// Homogeneous participating medium filling a closed boundary object,
+/// like smoke or a cloud. Light scatters inside with the phase function
+/// material after exponentially distributed distances. Rays never hit
+/// the medium itself, integrators sampling it with `sample_medium`.
+pub struct ConstantMedium<T: Hittable + Send + Sync + ?Sized> {
+    boundary: Arc<T>,
+    /// Extinction coefficient per scene unit
+    density: f64,
+    phase: Arc<dyn Material + Send + Sync>,
+}
+
+impl<T: Hittable + Send + Sync + ?Sized> ConstantMedium<T> {
+    pub fn new(boundary: Arc<T>, density: f64, phase: Arc<dyn Material + Send + Sync>) -> Self {
+        ConstantMedium {
+            boundary,
+            density,
+            phase,
+        }
+    }
+}
+
+impl<T: Hittable + Send + Sync + ?Sized> Hittable for ConstantMedium<T> {
+    fn hit(&self, _: &Ray, _: f64, _: f64) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    fn sample_medium(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord<'_>> {
+        // Segment of the ray inside the boundary, which may start
+        // before the ray when it is already inside
+        let enter = self.boundary.hit(ray, f64::NEG_INFINITY, f64::INFINITY)?.t;
+        let exit = self.boundary.hit(ray, enter + 0.0001, f64::INFINITY)?.t;
+        let (enter, exit) = (f64::max(enter, t_min), f64::min(exit, t_max));
+        if enter >= exit {
+            return None;
+        }
+
+        let length = ray.direction.length();
+        let distance = -(1.0 - sampler.get_1d()).ln() / self.density;
+        if distance > (exit - enter) * length {
+            return None;
+        }
+        Some(medium_hit(
+            ray,
+            enter + distance / length,
+            self.phase.as_ref(),
+        ))
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        self.boundary.bounding_box(time0, time1)
+    }
+}
+
+/// Homogeneous fog filling the scene below `height`, for atmospheric
+/// haze and light shafts. Rays above the fog reach the sky.
+pub struct GlobalFog<T: Hittable> {
+    world: T,
+    density: f64,
+    height: f64,
+    phase: Arc<dyn Material + Send + Sync>,
+}
+
+impl<T: Hittable> GlobalFog<T> {
+    /// No fog at all for a zero `density`
+    pub fn new(
+        world: T,
+        density: f64,
+        height: f64,
+        phase: Arc<dyn Material + Send + Sync>,
+    ) -> Self {
+        GlobalFog {
+            world,
+            density,
+            height,
+            phase,
+        }
+    }
+
+    /// Part of `t_min..t_max` along the ray below the top of the fog
+    fn fog_interval(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let dy = ray.direction.y();
+        let (start, end) = if dy == 0.0 {
+            match ray.origin.y() <= self.height {
+                true => (t_min, t_max),
+                false => return None,
+            }
+        } else {
+            // Where the ray crosses the top of the fog
+            let t = (self.height - ray.origin.y()) / dy;
+            match dy > 0.0 {
+                true => (t_min, f64::min(t, t_max)),
+                false => (f64::max(t, t_min), t_max),
+            }
+        };
+        match start < end {
+            true => Some((start, end)),
+            false => None,
+        }
+    }
+}
+
+impl<T: Hittable> Hittable for GlobalFog<T> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.world.hit(ray, t_min, t_max)
+    }
+
+    fn sample_medium(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord<'_>> {
+        // Media of the scene scattering before the fog win
+        let inner = self.world.sample_medium(ray, t_min, t_max, sampler);
+        if self.density <= 0.0 {
+            return inner;
+        }
+        let end = inner.as_ref().map_or(t_max, |rec| rec.t);
+        let (start, end) = match self.fog_interval(ray, t_min, end) {
+            Some(interval) => interval,
+            None => return inner,
+        };
+
+        let length = ray.direction.length();
+        let distance = -(1.0 - sampler.get_1d()).ln() / self.density;
+        if distance / length < end - start {
+            return Some(medium_hit(
+                ray,
+                start + distance / length,
+                self.phase.as_ref(),
+            ));
+        }
+        inner
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        match self.density > 0.0 {
+            true => None,
+            false => self.world.bounding_box(time0, time1),
+        }
+    }
+}
+
+/// Heterogeneous medium with the densities of a voxel grid stretched
+/// over `bounds`, for clouds and smoke. Free flights are sampled with
+/// delta tracking against the largest density of the grid, so rays
+/// missing the box cost a single slab test.
+pub struct GridMedium {
+    grid: Arc<DensityGrid>,
+    bounds: Aabb,
+    /// Extinction per scene unit for a voxel value of 1
+    scale: f64,
+    phase: Arc<dyn Material + Send + Sync>,
+}
+
+impl GridMedium {
+    pub fn new(
+        grid: Arc<DensityGrid>,
+        bounds: Aabb,
+        scale: f64,
+        phase: Arc<dyn Material + Send + Sync>,
+    ) -> Self {
+        GridMedium {
+            grid,
+            bounds,
+            scale,
+            phase,
+        }
+    }
+
+    fn extinction(&self, p: &Point3<f64>) -> f64 {
+        let local = |axis: usize| {
+            (p[axis] - self.bounds.min[axis]) / (self.bounds.max[axis] - self.bounds.min[axis])
+        };
+        self.scale * self.grid.density(local(0), local(1), local(2))
+    }
+
+    /// Fraction of the light going through the medium between `t_min` and
+    /// `t_max` along the ray, estimated without bias by ratio tracking
+    pub fn transmittance(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut dyn Sampler,
+    ) -> f64 {
+        let majorant = self.scale * self.grid.max();
+        let (mut t, end) = match self.bounds.interval(ray, t_min, t_max) {
+            Some(interval) if majorant > 0.0 => interval,
+            _ => return 1.0,
+        };
+
+        let length = ray.direction.length();
+        let mut transmittance = 1.0;
+        loop {
+            t -= (1.0 - sampler.get_1d()).ln() / (majorant * length);
+            if t >= end {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.extinction(&ray.at(t)) / majorant;
+        }
+    }
+}
+
+impl Hittable for GridMedium {
+    fn hit(&self, _: &Ray, _: f64, _: f64) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    fn sample_medium(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord<'_>> {
+        let majorant = self.scale * self.grid.max();
+        let (mut t, end) = match self.bounds.interval(ray, t_min, t_max) {
+            Some(interval) if majorant > 0.0 => interval,
+            _ => return None,
+        };
+
+        // Tentative collisions against the majorant are real
+        // with the ratio of the local density
+        let length = ray.direction.length();
+        loop {
+            t -= (1.0 - sampler.get_1d()).ln() / (majorant * length);
+            if t >= end {
+                return None;
+            }
+            if sampler.get_1d() * majorant < self.extinction(&ray.at(t)) {
+                return Some(medium_hit(ray, t, self.phase.as_ref()));
+            }
+        }
+    }
+
+    fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConstantMedium, GlobalFog, GridMedium};
+    use crate::color::Color;
+    use crate::libcore::aabb::Aabb;
+    use crate::libcore::hit::Hittable;
+    use crate::libcore::hittable_list::HittableList;
+    use crate::libcore::material::{Isotropic, Lambertian};
+    use crate::libcore::sampler::{IndependentSampler, Sampler};
+    use crate::libcore::volume::DensityGrid;
+    use crate::math::sphere::Sphere;
+    use crate::math::{Point3, Ray, Vec3};
+    use std::sync::Arc;
+
+    /// Ray along the z axis, going through the unit cube and sphere
+    fn down_z() -> Ray {
+        Ray::new(
+            Point3::with_values(0.0, 0.0, 5.0),
+            Vec3::with_values(0.0, 0.0, -1.0),
+        )
+    }
+
+    /// Fraction of rays along the diameter of the unit sphere
+    /// getting through without scattering
+    fn escaped(medium: &dyn Hittable) -> f64 {
+        let ray = down_z();
+        let mut sampler = IndependentSampler::new(0);
+        let n = 20000;
+        let mut passed = 0;
+        for i in 0..n {
+            sampler.start_pixel_sample((0, 0), i);
+            match medium.sample_medium(&ray, 0.001, f64::MAX, &mut sampler) {
+                Some(rec) => assert!(rec.p.z().abs() <= 1.0 + 1e-9),
+                None => passed += 1,
+            }
+        }
+        passed as f64 / n as f64
+    }
+
+    /// Mean ratio tracking estimate along the z axis
+    fn mean_transmittance(medium: &GridMedium) -> f64 {
+        let mut sampler = IndependentSampler::new(0);
+        let n = 20000;
+        let sum: f64 = (0..n)
+            .map(|i| {
+                sampler.start_pixel_sample((0, 0), i);
+                medium.transmittance(&down_z(), 0.001, f64::MAX, &mut sampler)
+            })
+            .sum();
+        sum / n as f64
+    }
+
+    fn unit_cube() -> Aabb {
+        Aabb::new(
+            Point3::with_values(-1.0, -1.0, -1.0),
+            Point3::with_values(1.0, 1.0, 1.0),
+        )
+    }
+
+    #[test]
+    fn ratio_tracking_follows_beer_lambert() {
+        let grid = Arc::new(DensityGrid::from_fn(2, 2, 2, |_, _, _| 1.0));
+        let phase = Arc::new(Isotropic::new(Color::with_values(1.0, 1.0, 1.0)));
+        let medium = GridMedium::new(grid, unit_cube(), 0.7, phase);
+        let expected = (-0.7 * 2.0f64).exp();
+        assert!((mean_transmittance(&medium) - expected).abs() < 0.01);
+        // Only the part of the ray inside the bounds counts
+        let mut sampler = IndependentSampler::new(0);
+        let outside = medium.transmittance(&down_z(), 0.001, 3.5, &mut sampler);
+        assert_eq!(outside, 1.0);
+    }
+
+    #[test]
+    fn media_follow_beer_lambert() {
+        let boundary = Arc::new(Sphere::new(
+            Point3::new(),
+            1.0,
+            Arc::new(Lambertian::new(Color::new())),
+        ));
+        let density = 0.7;
+        let phase = Arc::new(Isotropic::new(Color::with_values(1.0, 1.0, 1.0)));
+        let medium = Arc::new(ConstantMedium::new(boundary, density, phase));
+        let expected = (-density * 2.0f64).exp();
+        assert!((escaped(medium.as_ref()) - expected).abs() < 0.01);
+
+        // Overlapping media add up their densities
+        let mut both: HittableList<dyn Hittable + Send + Sync> = HittableList::new();
+        both.add(medium.clone());
+        both.add(medium);
+        assert!((escaped(&both) - expected * expected).abs() < 0.01);
+    }
+
+    #[test]
+    fn fog_stays_below_its_top() {
+        let world: HittableList<dyn Hittable + Send + Sync> = HittableList::new();
+        let phase = Arc::new(Isotropic::new(Color::with_values(1.0, 1.0, 1.0)));
+        let fog = GlobalFog::new(world, 0.5, 2.0, phase);
+        let mut sampler = IndependentSampler::new(0);
+        let up = Ray::new(
+            Point3::with_values(0.0, 5.0, 0.0),
+            Vec3::with_values(0.0, 1.0, 0.0),
+        );
+        assert!(fog
+            .sample_medium(&up, 0.001, f64::MAX, &mut sampler)
+            .is_none());
+        for i in 0..100 {
+            let ray = Ray::new(
+                Point3::with_values(0.0, 0.0, i as f64),
+                Vec3::with_values(1.0, 0.2, 0.0),
+            );
+            sampler.start_pixel_sample((0, 0), i);
+            if let Some(rec) = fog.sample_medium(&ray, 0.001, f64::MAX, &mut sampler) {
+                assert!(rec.p.y() <= 2.0 + 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn tracking_matches_the_optical_depth() {
+        // Density ramping up along z
+        let grid = Arc::new(DensityGrid::from_fn(4, 4, 16, |_, _, z| z));
+        let scale = 1.5;
+        let phase = Arc::new(Isotropic::new(Color::with_values(1.0, 1.0, 1.0)));
+        let medium = GridMedium::new(grid.clone(), unit_cube(), scale, phase);
+
+        let steps = 10000;
+        let depth = (0..steps)
+            .map(|i| grid.density(0.5, 0.5, (i as f64 + 0.5) / steps as f64))
+            .sum::<f64>()
+            * scale
+            * 2.0
+            / steps as f64;
+        assert!((escaped(&medium) - (-depth).exp()).abs() < 0.01);
+        assert!((mean_transmittance(&medium) - (-depth).exp()).abs() < 0.01);
+    }
+}
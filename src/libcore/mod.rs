@@ -1,4 +1,15 @@
+pub mod aabb;
+pub mod adaptive;
+pub mod aov;
+pub mod denoise;
+pub mod exr;
+pub mod film;
 pub mod hit;
 pub mod hittable_list;
 pub mod camera;
 pub mod material;
+pub mod medium;
+pub mod motion;
+pub mod sampler;
+pub mod texture;
+pub mod volume;
@@ -0,0 +1,126 @@
+use super::aabb::Aabb;
+use super::hit::{HitRecord, Hittable};
+use super::sampler::Sampler;
+use crate::math::{Ray, Vec3};
+use std::sync::Arc;
+
+/// Time-varying translation of any object: moved by `offset0` at
+/// `time0` and by `offset1` at `time1`, linearly in between
+pub struct Moving<T: Hittable + Send + Sync + ?Sized> {
+    object: Arc<T>,
+    offset0: Vec3<f64>,
+    offset1: Vec3<f64>,
+    time0: f64,
+    time1: f64,
+}
+
+impl<T: Hittable + Send + Sync + ?Sized> Moving<T> {
+    pub fn new(
+        object: Arc<T>,
+        offset0: Vec3<f64>,
+        offset1: Vec3<f64>,
+        time0: f64,
+        time1: f64,
+    ) -> Self {
+        Moving {
+            object,
+            offset0,
+            offset1,
+            time0,
+            time1,
+        }
+    }
+
+    pub fn offset(&self, time: f64) -> Vec3<f64> {
+        let duration = self.time1 - self.time0;
+        if duration == 0.0 {
+            return self.offset0;
+        }
+        self.offset0 + (self.offset1 - self.offset0) * ((time - self.time0) / duration)
+    }
+}
+
+impl<T: Hittable + Send + Sync + ?Sized> Hittable for Moving<T> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        // Move the ray instead of the object
+        let offset = self.offset(ray.time);
+        let moved = Ray::with_time(ray.origin - offset, ray.direction, ray.time);
+        self.object.hit(&moved, t_min, t_max).map(|mut rec| {
+            rec.p = rec.p + offset;
+            rec
+        })
+    }
+
+    fn sample_medium(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord<'_>> {
+        let offset = self.offset(ray.time);
+        let moved = Ray::with_time(ray.origin - offset, ray.direction, ray.time);
+        self.object
+            .sample_medium(&moved, t_min, t_max, sampler)
+            .map(|mut rec| {
+                rec.p = rec.p + offset;
+                rec
+            })
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        // Translations are linear so the two ends of the interval suffice
+        let b = self.object.bounding_box(time0, time1)?;
+        let (o0, o1) = (self.offset(time0), self.offset(time1));
+        Some(Aabb::surrounding(
+            &Aabb::new(b.min + o0, b.max + o0),
+            &Aabb::new(b.min + o1, b.max + o1),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Moving;
+    use crate::color::Color;
+    use crate::libcore::hit::Hittable;
+    use crate::libcore::material::Lambertian;
+    use crate::math::moving_sphere::MovingSphere;
+    use crate::math::sphere::Sphere;
+    use crate::math::{Point3, Ray, Vec3};
+    use std::sync::Arc;
+
+    #[test]
+    fn objects_follow_the_ray_time() {
+        let mat = Arc::new(Lambertian::new(Color::with_values(0.5, 0.5, 0.5)));
+        let sphere = Arc::new(Sphere::new(Point3::new(), 1.0, mat.clone()));
+        let moving = Moving::new(sphere, Vec3::new(), Vec3::with_values(0.0, 4.0, 0.0), 0.0, 1.0);
+        let moving_sphere = MovingSphere::new(
+            Point3::new(),
+            Point3::with_values(0.0, 4.0, 0.0),
+            0.0,
+            1.0,
+            1.0,
+            mat,
+        );
+        let objects: [&dyn Hittable; 2] = [&moving, &moving_sphere];
+
+        for object in objects.iter() {
+            let ray = |time| {
+                Ray::with_time(
+                    Point3::with_values(0.0, 4.0, -5.0),
+                    Vec3::with_values(0.0, 0.0, 1.0),
+                    time,
+                )
+            };
+            assert!(object.hit(&ray(0.0), 0.001, f64::MAX).is_none());
+            let rec = object.hit(&ray(1.0), 0.001, f64::MAX).unwrap();
+            assert!((rec.p - Point3::with_values(0.0, 4.0, -1.0)).length() < 1e-9);
+
+            // The box covers the whole motion
+            let aabb = object.bounding_box(0.0, 1.0).unwrap();
+            assert!((aabb.min - Point3::with_values(-1.0, -1.0, -1.0)).length() < 1e-9);
+            assert!((aabb.max - Point3::with_values(1.0, 5.0, 1.0)).length() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use super::{hash, to_unit, Sampler};
+
+/// First primes, one Halton base per dimension
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89,
+    97, 101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Halton sequence, dimension `d` being the radical inverse of the sample
+/// index in the `d`th prime base. Every pixel and dimension gets its own
+/// random toroidal shift (Cranley-Patterson rotation) so that pixels
+/// don't share the same pattern. Dimensions past the supported bases
+/// fall back to hashed random numbers.
+pub struct HaltonSampler {
+    seed: u64,
+    pixel_seed: u64,
+    sample_index: u64,
+    dimension: usize,
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.pixel_seed = hash(&[self.seed, pixel.0 as u64, pixel.1 as u64]);
+        self.sample_index = sample_index as u64;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let shift = to_unit(hash(&[self.pixel_seed, dimension as u64]) as u32);
+        match PRIMES.get(dimension) {
+            Some(&base) => (radical_inverse(base, self.sample_index) + shift).fract(),
+            None => to_unit(
+                hash(&[self.pixel_seed, dimension as u64, self.sample_index]) as u32,
+            ),
+        }
+    }
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> HaltonSampler {
+        HaltonSampler {
+            seed,
+            pixel_seed: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+/// Mirrors the digits of `index` written in `base` around the radix point
+pub fn radical_inverse(base: u64, mut index: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_n = 1.0;
+    let mut reversed = 0u64;
+    while index > 0 {
+        let next = index / base;
+        reversed = reversed * base + (index - next * base);
+        inv_base_n *= inv_base;
+        index = next;
+    }
+    f64::min(reversed as f64 * inv_base_n, 1.0 - f64::EPSILON)
+}
@@ -0,0 +1,33 @@
+use super::{hash, Sampler};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Uniform random numbers with no relation between samples
+pub struct IndependentSampler {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.rng = StdRng::seed_from_u64(hash(&[
+            self.seed,
+            pixel.0 as u64,
+            pixel.1 as u64,
+            sample_index as u64,
+        ]));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen::<f64>()
+    }
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> IndependentSampler {
+        IndependentSampler {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
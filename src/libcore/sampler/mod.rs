@@ -0,0 +1,156 @@
+mod halton;
+mod independent;
+mod sobol;
+mod stratified;
+
+pub use halton::HaltonSampler;
+pub use independent::IndependentSampler;
+pub use sobol::SobolSampler;
+pub use stratified::StratifiedSampler;
+
+/// Source of the random numbers used while tracing a camera ray.
+/// Every call hands out the next dimension of the current sample so
+/// that well distributed sequences can be spread over the pixel area,
+/// the lens and every bounce of the path.
+///
+/// Values only depend on the sampler seed, the pixel, the sample index
+/// and the dimension, which makes renders reproducible whatever the
+/// number of threads and the order pixels are rendered in.
+pub trait Sampler {
+    /// Starts the `sample_index`th sample of `pixel` (column, row)
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize);
+
+    /// Next sample dimension in [0, 1)
+    fn get_1d(&mut self) -> f64;
+
+    /// Next two sample dimensions, stratified together when possible
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerKind {
+    /// Builds a sampler for one rendering thread,
+    /// `samples_per_pixel` being the most a pixel will ask for
+    pub fn create(&self, samples_per_pixel: usize, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerKind::Stratified => {
+                Box::new(StratifiedSampler::new(samples_per_pixel, seed))
+            }
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+        }
+    }
+}
+
+/// 64 bit finalizer from MurmurHash3
+#[inline]
+pub(crate) fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 33;
+    v = v.wrapping_mul(0xff51afd7ed558ccd);
+    v ^= v >> 33;
+    v = v.wrapping_mul(0xc4ceb9fe1a85ec53);
+    v ^ (v >> 33)
+}
+
+#[inline]
+pub(crate) fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x9e3779b97f4a7c15, |h, v| mix_bits(h ^ mix_bits(*v)))
+}
+
+/// Maps 32 random bits to [0, 1)
+#[inline]
+pub(crate) fn to_unit(v: u32) -> f64 {
+    v as f64 / 4294967296.0
+}
+
+/// Element `i` of a random permutation of [0, l) picked by `p`,
+/// computed without storing the permutation (Kensler, Correlated
+/// Multi-Jittered Sampling)
+pub(crate) fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % l
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{permutation_element, Sampler, SamplerKind};
+
+    fn draw(sampler: &mut dyn Sampler, pixel: (usize, usize), index: usize) -> Vec<f64> {
+        sampler.start_pixel_sample(pixel, index);
+        (0..40).map(|_| sampler.get_1d()).collect()
+    }
+
+    /// Samples must not depend on what the sampler was used for before,
+    /// rendering threads pick pixels in no particular order
+    #[test]
+    fn samples_only_depend_on_seed_pixel_and_index() {
+        let kinds = [
+            SamplerKind::Independent,
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+        ];
+        for kind in kinds.iter() {
+            let mut a = kind.create(16, 42);
+            let mut b = kind.create(16, 42);
+            draw(b.as_mut(), (7, 3), 5);
+            draw(b.as_mut(), (1, 2), 0);
+            assert_eq!(draw(a.as_mut(), (1, 2), 3), draw(b.as_mut(), (1, 2), 3));
+
+            let mut c = kind.create(16, 43);
+            assert_ne!(draw(a.as_mut(), (1, 2), 3), draw(c.as_mut(), (1, 2), 3));
+        }
+    }
+
+    #[test]
+    fn permutation_is_a_bijection() {
+        for &(l, p) in &[(1, 7), (5, 0x1234), (16, 99), (100, 0xdeadbeef)] {
+            let mut seen = vec![false; l as usize];
+            for i in 0..l {
+                let e = permutation_element(i, l, p) as usize;
+                assert!(!seen[e]);
+                seen[e] = true;
+            }
+        }
+    }
+}
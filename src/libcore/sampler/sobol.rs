@@ -0,0 +1,151 @@
+use super::{hash, to_unit, Sampler};
+
+/// Primitive polynomials and initial direction numbers of the first
+/// Sobol dimensions after the van der Corput one, as (degree,
+/// coefficients, m) from Joe and Kuo's new-joe-kuo-6.21201 table
+const DIRECTIONS: [(u32, u32, &[u32]); 15] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+];
+
+/// Sobol sequence with Owen scrambling (Laine-Karras style hash,
+/// seeded per pixel and dimension). Dimensions past the
+/// supported ones fall back to hashed random numbers.
+pub struct SobolSampler {
+    seed: u64,
+    matrices: Vec<[u32; 32]>,
+    pixel_seed: u64,
+    sample_index: u64,
+    dimension: usize,
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.pixel_seed = hash(&[self.seed, pixel.0 as u64, pixel.1 as u64]);
+        self.sample_index = sample_index as u64;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let seed = hash(&[self.pixel_seed, dimension as u64]) as u32;
+        match self.matrices.get(dimension) {
+            Some(matrix) => to_unit(owen_scramble(sobol(matrix, self.sample_index), seed)),
+            None => to_unit(
+                hash(&[self.pixel_seed, dimension as u64, self.sample_index]) as u32,
+            ),
+        }
+    }
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> SobolSampler {
+        let mut matrices = vec![van_der_corput()];
+        matrices.extend(
+            DIRECTIONS
+                .iter()
+                .map(|(degree, coefficients, m)| direction_numbers(*degree, *coefficients, m)),
+        );
+        SobolSampler {
+            seed,
+            matrices,
+            pixel_seed: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+fn van_der_corput() -> [u32; 32] {
+    let mut v = [0; 32];
+    for (k, v) in v.iter_mut().enumerate() {
+        *v = 1 << (31 - k);
+    }
+    v
+}
+
+/// Expands the initial direction numbers with the recurrence
+/// given by the primitive polynomial
+fn direction_numbers(degree: u32, coefficients: u32, m: &[u32]) -> [u32; 32] {
+    let s = degree as usize;
+    let mut v = [0u32; 32];
+    for k in 0..s {
+        v[k] = m[k] << (31 - k);
+    }
+    for k in s..32 {
+        v[k] = v[k - s] ^ (v[k - s] >> s);
+        for j in 1..s {
+            if (coefficients >> (s - 1 - j)) & 1 == 1 {
+                v[k] ^= v[k - j];
+            }
+        }
+    }
+    v
+}
+
+/// `index`th point of the sequence defined by `matrix`, as 32 fixed point bits
+pub fn sobol(matrix: &[u32; 32], mut index: u64) -> u32 {
+    let mut v = 0;
+    let mut k = 0;
+    while index != 0 && k < 32 {
+        if index & 1 == 1 {
+            v ^= matrix[k];
+        }
+        index >>= 1;
+        k += 1;
+    }
+    v
+}
+
+/// Nested uniform scrambling approximation: randomly flips digits
+/// depending only on the more significant ones, which keeps the
+/// stratification properties of the sequence
+fn owen_scramble(v: u32, seed: u32) -> u32 {
+    let mut v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x05526c56);
+    v ^= v.wrapping_mul(0x53a22864);
+    v.reverse_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{owen_scramble, sobol, SobolSampler};
+
+    /// The first 16 points of the first two dimensions are a (0, 4, 2)-net:
+    /// every cell of a 4x4 grid (as well as 2x8, 8x2...) holds exactly one
+    #[test]
+    fn first_dimensions_are_stratified() {
+        let sampler = SobolSampler::new(0);
+        for &seed in &[None, Some(0x1234_5678), Some(0xdead_beef)] {
+            let mut cells = [false; 16];
+            for i in 0..16 {
+                let mut x = sobol(&sampler.matrices[0], i);
+                let mut y = sobol(&sampler.matrices[1], i);
+                if let Some(seed) = seed {
+                    x = owen_scramble(x, seed);
+                    y = owen_scramble(y, seed ^ 0x9e37_79b9);
+                }
+                let cell = (x >> 30) as usize * 4 + (y >> 30) as usize;
+                assert!(!cells[cell]);
+                cells[cell] = true;
+            }
+        }
+    }
+}
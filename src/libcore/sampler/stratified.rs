@@ -0,0 +1,68 @@
+use super::{hash, permutation_element, Sampler};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Jittered sampling: every dimension is split in as many strata as
+/// there are samples per pixel (a grid for 2D requests) and each sample
+/// falls in its own stratum. Strata are visited in a different random
+/// order for every pixel and dimension so dimensions stay uncorrelated.
+pub struct StratifiedSampler {
+    samples: u32,
+    x_strata: u32,
+    y_strata: u32,
+    seed: u64,
+    pixel_seed: u64,
+    sample_index: u32,
+    dimension: u64,
+    rng: StdRng,
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: usize) {
+        self.pixel_seed = hash(&[self.seed, pixel.0 as u64, pixel.1 as u64]);
+        self.sample_index = sample_index as u32;
+        self.dimension = 0;
+        self.rng = StdRng::seed_from_u64(hash(&[self.pixel_seed, sample_index as u64]));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let stratum = self.stratum(self.samples);
+        (stratum as f64 + self.rng.gen::<f64>()) / self.samples as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let stratum = self.stratum(self.x_strata * self.y_strata);
+        let (x, y) = (stratum % self.x_strata, stratum / self.x_strata);
+        (
+            (x as f64 + self.rng.gen::<f64>()) / self.x_strata as f64,
+            (y as f64 + self.rng.gen::<f64>()) / self.y_strata as f64,
+        )
+    }
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> StratifiedSampler {
+        let samples = usize::max(samples_per_pixel, 1) as u32;
+        let x_strata = ((samples as f64).sqrt().round() as u32).max(1);
+        let y_strata = (samples as f64 / x_strata as f64).ceil() as u32;
+        StratifiedSampler {
+            samples,
+            x_strata,
+            y_strata,
+            seed,
+            pixel_seed: 0,
+            sample_index: 0,
+            dimension: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Stratum of the current sample among `count`, samples past
+    /// the expected count start over in a new order
+    fn stratum(&mut self, count: u32) -> u32 {
+        let round = (self.sample_index / count) as u64;
+        let seed = hash(&[self.pixel_seed, self.dimension, round]) as u32;
+        self.dimension += 1;
+        permutation_element(self.sample_index % count, count, seed)
+    }
+}
@@ -0,0 +1,84 @@
+use crate::color::Color;
+use crate::math::{clamp, Point3};
+use std::path::Path;
+
+/// Spatially varying material parameter, looked up with the surface
+/// coordinates and the position of a hit
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, p: &Point3<f64>) -> Color<f64>;
+}
+
+/// Constant grey
+impl Texture for f64 {
+    fn value(&self, _: f64, _: f64, _: &Point3<f64>) -> Color<f64> {
+        Color::with_values(*self, *self, *self)
+    }
+}
+
+impl Texture for Color<f64> {
+    fn value(&self, _: f64, _: f64, _: &Point3<f64>) -> Color<f64> {
+        *self
+    }
+}
+
+/// Picture wrapped over the surface coordinates, the bottom
+/// left corner being at (0, 0)
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    /// Linear colors, row-major, top row first
+    texels: Vec<Color<f64>>,
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, texels: Vec<Color<f64>>) -> Self {
+        assert_eq!(texels.len(), width * height, "Texture size mismatch");
+        ImageTexture {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    /// Loads an sRGB encoded picture
+    pub fn from_file<P: AsRef<Path>>(path: P) -> image::ImageResult<Self> {
+        ImageTexture::load(path, srgb_to_linear)
+    }
+
+    /// Loads a picture holding data rather than colors, like normal maps
+    pub fn from_file_linear<P: AsRef<Path>>(path: P) -> image::ImageResult<Self> {
+        ImageTexture::load(path, |c| c)
+    }
+
+    fn load<P: AsRef<Path>>(path: P, transfer: fn(f64) -> f64) -> image::ImageResult<Self> {
+        let img = image::open(path)?.to_rgb8();
+        let decode = |c: u8| transfer(c as f64 / 255.0);
+        let texels = img
+            .pixels()
+            .map(|p| Color::with_values(decode(p[0]), decode(p[1]), decode(p[2])))
+            .collect();
+        Ok(ImageTexture::new(
+            img.width() as usize,
+            img.height() as usize,
+            texels,
+        ))
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _: &Point3<f64>) -> Color<f64> {
+        // Nearest texel, clamped to the borders
+        let i = (clamp(u, 0.0, 1.0) * self.width as f64) as usize;
+        let j = (clamp(1.0 - v, 0.0, 1.0) * self.height as f64) as usize;
+        let i = usize::min(i, self.width - 1);
+        let j = usize::min(j, self.height - 1);
+        self.texels[j * self.width + i]
+    }
+}
+
+fn srgb_to_linear(c: f64) -> f64 {
+    match c <= 0.04045 {
+        true => c / 12.92,
+        false => ((c + 0.055) / 1.055).powf(2.4),
+    }
+}
@@ -0,0 +1,132 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Dense 3D array of densities for clouds and smoke. Voxel values sit
+/// at cell centers of the unit cube and are interpolated trilinearly.
+pub struct DensityGrid {
+    size: [usize; 3],
+    /// x fastest, then y, then z
+    values: Vec<f64>,
+    max: f64,
+}
+
+impl DensityGrid {
+    pub fn new(nx: usize, ny: usize, nz: usize, values: Vec<f64>) -> Self {
+        assert!(nx > 0 && ny > 0 && nz > 0, "Empty density grid");
+        assert_eq!(values.len(), nx * ny * nz, "Density grid size mismatch");
+        let max = values.iter().cloned().fold(0.0, f64::max);
+        DensityGrid {
+            size: [nx, ny, nz],
+            values,
+            max,
+        }
+    }
+
+    /// Grid sampling `f` at the voxel centers of the unit cube
+    pub fn from_fn<F: Fn(f64, f64, f64) -> f64>(nx: usize, ny: usize, nz: usize, f: F) -> Self {
+        let mut values = Vec::with_capacity(nx * ny * nz);
+        for k in 0..nz {
+            for j in 0..ny {
+                for i in 0..nx {
+                    values.push(f(
+                        (i as f64 + 0.5) / nx as f64,
+                        (j as f64 + 0.5) / ny as f64,
+                        (k as f64 + 0.5) / nz as f64,
+                    ));
+                }
+            }
+        }
+        DensityGrid::new(nx, ny, nz, values)
+    }
+
+    /// Reads a raw grid: a text line 'nx ny nz' followed by the
+    /// nx * ny * nz densities as little endian f32, x fastest
+    pub fn from_file<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let invalid = |message: &str| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid density grid: {}", message),
+            )
+        };
+
+        let bytes = fs::read(path)?;
+        let header_end = bytes
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid("missing header"))?;
+        let size = String::from_utf8_lossy(&bytes[..header_end])
+            .split_whitespace()
+            .map(|v| v.parse::<usize>())
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(|_| invalid("bad header"))?;
+        if size.len() != 3 || size.contains(&0) {
+            return Err(invalid("bad header"));
+        }
+
+        let data = &bytes[header_end + 1..];
+        if data.len() != size[0] * size[1] * size[2] * 4 {
+            return Err(invalid("size does not match the header"));
+        }
+        let values = data
+            .chunks(4)
+            .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f64)
+            .collect::<Vec<f64>>();
+        if values.iter().any(|v| !v.is_finite() || *v < 0.0) {
+            return Err(invalid("densities must be finite and positive"));
+        }
+        Ok(DensityGrid::new(size[0], size[1], size[2], values))
+    }
+
+    /// Largest voxel value, bounding every interpolated density
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    fn voxel(&self, i: usize, j: usize, k: usize) -> f64 {
+        self.values[(k * self.size[1] + j) * self.size[0] + i]
+    }
+
+    /// Trilinear lookup at a point of the unit cube, clamped at the borders
+    pub fn density(&self, x: f64, y: f64, z: f64) -> f64 {
+        let mut index = [0; 3];
+        let mut next = [0; 3];
+        let mut frac = [0.0; 3];
+        for (axis, &c) in [x, y, z].iter().enumerate() {
+            let n = self.size[axis];
+            let g = f64::max(c * n as f64 - 0.5, 0.0);
+            let i = usize::min(g as usize, n - 1);
+            index[axis] = i;
+            next[axis] = usize::min(i + 1, n - 1);
+            frac[axis] = f64::min(g - i as f64, 1.0);
+        }
+
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let along_x = |j: usize, k: usize| {
+            lerp(
+                self.voxel(index[0], j, k),
+                self.voxel(next[0], j, k),
+                frac[0],
+            )
+        };
+        let along_y = |k: usize| lerp(along_x(index[1], k), along_x(next[1], k), frac[1]);
+        lerp(along_y(index[2]), along_y(next[2]), frac[2])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DensityGrid;
+
+    #[test]
+    fn trilinear_lookup_reproduces_linear_fields() {
+        let field = |x: f64, y: f64, z: f64| 1.0 + 2.0 * x - y + 0.5 * z;
+        let grid = DensityGrid::from_fn(8, 5, 6, field);
+        for &(x, y, z) in [(0.3, 0.5, 0.7), (0.15, 0.85, 0.2), (0.5, 0.5, 0.5)].iter() {
+            assert!((grid.density(x, y, z) - field(x, y, z)).abs() < 1e-12);
+        }
+        // Held constant past the outermost voxel centers
+        assert_eq!(grid.density(0.0, 0.5, 0.5), grid.density(0.0625, 0.5, 0.5));
+        assert!((grid.max() - field(0.9375, 0.1, 11.0 / 12.0)).abs() < 1e-12);
+    }
+}
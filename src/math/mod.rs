@@ -1,7 +1,10 @@
 pub mod ray;
+pub mod moving_sphere;
+pub mod onb;
 pub mod sphere;
 pub mod vec3;
 
+pub use self::onb::Onb;
 pub use self::ray::Ray;
 pub use self::vec3::Vec3;
 use super::rand::random;
@@ -98,6 +101,41 @@ pub fn random_unit_vector() -> Vec3<f64> {
     Vec3::with_values(r * a.cos(), r * a.sin(), z)
 }
 
+/// Maps a point of the unit square to the unit disk with Shirley's
+/// concentric mapping which keeps the stratification of the samples
+#[inline]
+pub fn sample_unit_disk(u: (f64, f64)) -> Vec3<f64> {
+    let a = 2.0 * u.0 - 1.0;
+    let b = 2.0 * u.1 - 1.0;
+    if a == 0.0 && b == 0.0 {
+        return Vec3::new();
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, std::f64::consts::FRAC_PI_4 * (b / a))
+    } else {
+        (
+            b,
+            std::f64::consts::FRAC_PI_2 - std::f64::consts::FRAC_PI_4 * (a / b),
+        )
+    };
+    Vec3::with_values(r * theta.cos(), r * theta.sin(), 0.)
+}
+
+/// Uniform direction on the unit sphere from a point of the unit square
+#[inline]
+pub fn sample_unit_vector(u: (f64, f64)) -> Vec3<f64> {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1f64 - z * z).max(0.0).sqrt();
+    let a = 2.0 * std::f64::consts::PI * u.1;
+    Vec3::with_values(r * a.cos(), r * a.sin(), z)
+}
+
+/// Uniform point inside the unit sphere, `w` picking the radius
+#[inline]
+pub fn sample_in_unit_sphere(u: (f64, f64), w: f64) -> Vec3<f64> {
+    sample_unit_vector(u) * w.cbrt()
+}
+
 #[inline]
 pub fn reflect(v: &Vec3<f64>, n: &Vec3<f64>) -> Vec3<f64> {
     (*v) - (*n) * v.dot(n) * 2.0
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+use super::sphere::{sphere_derivatives, sphere_uv};
+use super::Point3;
+use super::Ray;
+use super::Vec3;
+use crate::libcore::aabb::Aabb;
+use crate::libcore::hit::HitRecord;
+use crate::libcore::hit::Hittable;
+use crate::libcore::material::Material;
+
+/// Sphere whose center moves linearly from `center0` at `time0`
+/// to `center1` at `time1`, keeping on the same line outside
+pub struct MovingSphere {
+    center0: Point3<f64>,
+    center1: Point3<f64>,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    material: Arc<dyn Material + Send + Sync>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Point3<f64>,
+        center1: Point3<f64>,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Self {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+
+    pub fn center(&self, time: f64) -> Point3<f64> {
+        let duration = self.time1 - self.time0;
+        if duration == 0.0 {
+            return self.center0;
+        }
+        self.center0 + (self.center1 - self.center0) * ((time - self.time0) / duration)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let center = self.center(ray.time);
+        let oc = ray.origin - center;
+        let a = ray.direction.length_squared();
+        let half_b = oc.dot(ray.direction);
+        let c = oc.length_squared() - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant <= 0.0 {
+            return None;
+        }
+
+        // Same lifetime trick as Sphere
+        let mat_ptr = Arc::as_ptr(&self.material);
+        let temp = discriminant.sqrt();
+        for root in [(-half_b - temp) / a, (-half_b + temp) / a].iter() {
+            if *root < t_max && *root > t_min {
+                let p = ray.at(*root);
+                let outward_normal = (p - center) / self.radius;
+                let (u, v) = sphere_uv(&outward_normal);
+                let (dpdu, dpdv) = sphere_derivatives(&outward_normal, self.radius);
+                return Some(
+                    HitRecord::new_hit(p, *root, ray, &outward_normal, unsafe { &*mat_ptr })
+                        .with_uv(u, v)
+                        .with_derivatives(dpdu, dpdv),
+                );
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let r = Vec3::with_values(self.radius, self.radius, self.radius);
+        let (c0, c1) = (self.center(time0), self.center(time1));
+        Some(Aabb::surrounding(
+            &Aabb::new(c0 - r, c0 + r),
+            &Aabb::new(c1 - r, c1 + r),
+        ))
+    }
+}
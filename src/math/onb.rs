@@ -0,0 +1,49 @@
+use super::Vec3;
+
+/// Orthonormal basis, `w` being the surface normal when
+/// used as a shading frame
+#[derive(Clone, Copy, Debug)]
+pub struct Onb {
+    pub u: Vec3<f64>,
+    pub v: Vec3<f64>,
+    pub w: Vec3<f64>,
+}
+
+impl Onb {
+    /// Basis around the unit vector `w` (Duff et al. 2017, branchless)
+    pub fn from_w(w: Vec3<f64>) -> Self {
+        let sign = 1f64.copysign(w.z());
+        let a = -1.0 / (sign + w.z());
+        let b = w.x() * w.y() * a;
+        Onb {
+            u: Vec3::with_values(1.0 + sign * w.x() * w.x() * a, sign * b, -sign * w.x()),
+            v: Vec3::with_values(b, sign + w.y() * w.y() * a, -w.y()),
+            w,
+        }
+    }
+
+    /// Basis around the unit vector `w` with `u` as close as
+    /// possible to `tangent`
+    pub fn from_w_tangent(w: Vec3<f64>, tangent: Vec3<f64>) -> Self {
+        let u = tangent - w * w.dot(tangent);
+        if u.length_squared() < 1e-12 {
+            return Onb::from_w(w);
+        }
+        let u = u.unit_vec();
+        Onb {
+            u,
+            v: w.cross(&u),
+            w,
+        }
+    }
+
+    /// World direction of local coordinates `a`
+    pub fn local(&self, a: Vec3<f64>) -> Vec3<f64> {
+        self.u * a.x() + self.v * a.y() + self.w * a.z()
+    }
+
+    /// Local coordinates of the world direction `a`
+    pub fn to_local(&self, a: Vec3<f64>) -> Vec3<f64> {
+        Vec3::with_values(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
@@ -1,14 +1,35 @@
 use super::Point3;
 use super::Vec3;
 
+#[derive(Clone, Copy)]
 pub struct Ray {
     pub origin: Point3<f64>,
     pub direction: Vec3<f64>,
+    /// Instant at which the ray is traced, inside the camera shutter interval
+    pub time: f64,
+    /// Hero wavelength in nanometers when rendering spectrally. A
+    /// material returning a scattered ray with a wavelength tells that
+    /// its direction only holds for that wavelength, like dispersion.
+    pub wavelength: Option<f64>,
 }
 
 impl Ray {
     pub fn new(origin: Point3<f64>, direction: Vec3<f64>) -> Ray {
-        Ray { origin, direction }
+        Ray::with_time(origin, direction, 0.0)
+    }
+
+    pub fn with_time(origin: Point3<f64>, direction: Vec3<f64>, time: f64) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time,
+            wavelength: None,
+        }
+    }
+
+    pub fn with_wavelength(mut self, wavelength: Option<f64>) -> Ray {
+        self.wavelength = wavelength;
+        self
     }
 
     #[inline(never)]
@@ -1,7 +1,9 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 use super::Point3;
 use super::Ray;
 use super::Vec3;
+use crate::libcore::aabb::Aabb;
 use crate::libcore::hit::HitRecord;
 use crate::libcore::hit::Hittable;
 use crate::libcore::material::Material;
@@ -32,28 +34,57 @@ impl Hittable for Sphere {
             let root = (-half_b - temp) / a;
             if root < t_max && root > t_min {
                 let p = ray.at(root);
-                return Some(HitRecord::new_hit(
-                    p,
-                    root,
-                    ray,
-                    &((p - self.center) / self.radius),
-                    unsafe{ &*mat_ptr },
-                ));
+                let outward_normal = (p - self.center) / self.radius;
+                let (u, v) = sphere_uv(&outward_normal);
+                let (dpdu, dpdv) = sphere_derivatives(&outward_normal, self.radius);
+                return Some(
+                    HitRecord::new_hit(p, root, ray, &outward_normal, unsafe { &*mat_ptr })
+                        .with_uv(u, v)
+                        .with_derivatives(dpdu, dpdv),
+                );
             }
             let root = (-half_b + temp) / a;
             if root < t_max && root > t_min {
                 let p = ray.at(root);
-                return Some(HitRecord::new_hit(
-                    p,
-                    root,
-                    ray,
-                    &((p - self.center) / self.radius),
-                    unsafe{ &*mat_ptr },
-                ));
+                let outward_normal = (p - self.center) / self.radius;
+                let (u, v) = sphere_uv(&outward_normal);
+                let (dpdu, dpdv) = sphere_derivatives(&outward_normal, self.radius);
+                return Some(
+                    HitRecord::new_hit(p, root, ray, &outward_normal, unsafe { &*mat_ptr })
+                        .with_uv(u, v)
+                        .with_derivatives(dpdu, dpdv),
+                );
             }
         };
         None
     }
+
+    fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
+        let r = Vec3::with_values(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+}
+
+/// Longitude and latitude of a point of the unit sphere mapped to
+/// [0, 1], u going around the y axis from -x and v from the bottom
+pub fn sphere_uv(p: &Point3<f64>) -> (f64, f64) {
+    let theta = (-p.y()).acos();
+    let phi = (-p.z()).atan2(p.x()) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+/// Derivatives of the position along the `sphere_uv` coordinates for
+/// the unit point `n` of a sphere of the given radius. dp/du vanishes
+/// at the poles.
+pub fn sphere_derivatives(n: &Vec3<f64>, radius: f64) -> (Vec3<f64>, Vec3<f64>) {
+    let sin_theta = f64::max((1.0 - n.y() * n.y()).sqrt(), 1e-9);
+    let dpdu = Vec3::with_values(n.z(), 0.0, -n.x()) * (2.0 * PI * radius);
+    let dpdv = Vec3::with_values(
+        -n.x() * n.y() / sin_theta,
+        sin_theta,
+        -n.z() * n.y() / sin_theta,
+    ) * (PI * radius);
+    (dpdu, dpdv)
 }
 
 impl Sphere {
@@ -69,3 +100,25 @@ impl Sphere {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{sphere_derivatives, sphere_uv};
+    use crate::math::Vec3;
+
+    #[test]
+    fn derivatives_match_the_uv_mapping() {
+        let radius = 2.0;
+        let n = Vec3::with_values(0.3f64, -0.5, 0.6).unit_vec();
+        let (u, v) = sphere_uv(&n);
+        let (dpdu, dpdv) = sphere_derivatives(&n, radius);
+        // Moving along each derivative changes only its coordinate
+        let h = 1e-6;
+        let (u1, v1) = sphere_uv(&(n + dpdu * (h / radius)).unit_vec());
+        assert!(((u1 - u) / h - 1.0).abs() < 1e-4 && ((v1 - v) / h).abs() < 1e-4);
+        let (u2, v2) = sphere_uv(&(n + dpdv * (h / radius)).unit_vec());
+        assert!(((u2 - u) / h).abs() < 1e-4 && ((v2 - v) / h - 1.0).abs() < 1e-4);
+        // Outward orientation
+        assert!(dpdu.cross(&dpdv).dot(n) > 0.0);
+    }
+}
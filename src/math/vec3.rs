@@ -1,7 +1,6 @@
 use num::Num;
 use rand::distributions::Distribution;
 use rand::distributions::Standard;
-use rand::random;
 use std::convert::Into;
 use std::fmt::Debug;
 use std::ops::Add;
@@ -224,11 +223,11 @@ where
     T: Num + Copy + Into<f64> + Debug + Default,
     Standard: Distribution<T>,
 {
-    fn sample<R: rand::Rng + ?Sized>(&self, _: &mut R) -> Vec3<T>
+    fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Vec3<T>
     where
         R: rand::Rng,
     {
-        Vec3::with_values(random::<T>(), random::<T>(), random::<T>())
+        Vec3::with_values(rng.gen::<T>(), rng.gen::<T>(), rng.gen::<T>())
     }
 }
 
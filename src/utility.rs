@@ -1,17 +1,156 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
 use std::str::FromStr;
-use crate::libcore::camera::Camera;
+use crate::libcore::adaptive::AdaptiveSampling;
+use crate::libcore::aov::AovFormat;
+use crate::libcore::camera::{
+    read_prescription, ApertureMask, ApertureShape, Camera, CameraPath, FisheyeMapping,
+    Interpolation, LensElement, PhysicalCamera, Shutter, StereoLayout,
+};
+use crate::math::{degrees_to_radians, Point3};
+use std::sync::Arc;
+use crate::libcore::aabb::Aabb;
+use crate::libcore::film::{Filter, FilterKind};
+use crate::libcore::volume::DensityGrid;
+use crate::libcore::sampler::SamplerKind;
 
 extern crate clap;
 use clap::{App, Arg};
 
 #[derive(Clone, Copy)]
 pub struct ThreadData<'a> {
-    pub camera: &'a Camera,
+    pub camera: &'a (dyn Camera + Sync),
     pub aspect_ratio: f64,
     pub image_height: usize,
     pub image_width: usize,
     pub samples_per_pixel:usize,
-    pub max_depth: usize
+    pub max_depth: usize,
+    pub aov: Option<AovFormat>,
+    pub denoise: bool,
+    pub adaptive: Option<AdaptiveSampling>,
+    pub heatmap: Option<&'a str>,
+    pub sampler: SamplerKind,
+    pub seed: u64,
+    pub filter: Filter,
+    pub stereo: Option<StereoLayout>,
+    /// Multiplier applied to the radiance reaching the camera
+    pub exposure: f64,
+    /// Trace a few wavelengths per path instead of RGB
+    pub spectral: bool,
+}
+
+pub struct UserData {
+    pub camera: CameraKind,
+    /// Prescription of the lens system camera
+    pub lens_elements: Option<Vec<LensElement>>,
+    pub camera_path: Option<CameraPath>,
+    /// Number of frames of a turntable orbit
+    pub turntable: Option<usize>,
+    /// Frames to render, both included
+    pub frames: Option<(i64, i64)>,
+    /// Frame rate giving the scene time of every frame
+    pub fps: f64,
+    pub stereo: Option<StereoSettings>,
+    /// Motion blur is off without it, everything being rendered at time 0
+    pub shutter: Option<Shutter>,
+    pub lens: LensSettings,
+    /// Physical camera mode, driving exposure, depth of field and motion blur
+    pub physical: Option<PhysicalCamera>,
+    /// Luminance in cd/m² of a scene radiance of 1
+    pub luminance_unit: f64,
+    pub format: ImageFormat,
+    pub aov: Option<AovFormat>,
+    pub denoise: bool,
+    pub spectral: bool,
+    pub fog: Option<FogSettings>,
+    pub volume: Option<VolumeSettings>,
+    pub adaptive_threshold: Option<f64>,
+    pub min_samples: usize,
+    pub max_samples: Option<usize>,
+    pub heatmap: Option<String>,
+    pub sampler: SamplerKind,
+    pub seed: u64,
+    pub filter: Filter,
+}
+
+/// Projection picked with `--camera` or the `camera` line of a scene file
+#[derive(Clone, Copy)]
+pub enum CameraKind {
+    Perspective,
+    Orthographic { view_width: f64 },
+    Equirectangular,
+    Fisheye { fov: f64, mapping: FisheyeMapping },
+    Cubemap,
+    LensSystem,
+}
+
+impl CameraKind {
+    /// Aspect ratio required by the projection, if any
+    pub fn aspect_ratio(&self) -> Option<f64> {
+        match self {
+            CameraKind::Equirectangular => Some(2.0),
+            CameraKind::Cubemap => Some(3.0 / 2.0),
+            _ => None,
+        }
+    }
+
+    /// Image size closest to `width` pixels wide, cubemaps being
+    /// rounded down to whole square faces
+    pub fn image_size(&self, width: usize) -> (usize, usize) {
+        match self {
+            CameraKind::Cubemap => {
+                let face_size = usize::max(width / 3, 1);
+                (3 * face_size, 2 * face_size)
+            }
+            _ => {
+                let aspect_ratio = self.aspect_ratio().unwrap_or(3.0 / 2.0);
+                (width, (width as f64 / aspect_ratio) as usize)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct StereoSettings {
+    pub layout: StereoLayout,
+    /// Distance between the eyes in world units
+    pub interocular: f64,
+    /// Distance at which both eyes see the same image
+    pub convergence: f64,
+}
+
+/// Homogeneous fog filling the scene
+#[derive(Clone, Copy)]
+pub struct FogSettings {
+    /// Extinction per world unit
+    pub density: f64,
+    /// Altitude of the top of the fog
+    pub height: f64,
+    /// Henyey-Greenstein mean cosine
+    pub anisotropy: f64,
+}
+
+/// Voxel grid volume placed in the scene
+pub struct VolumeSettings {
+    pub grid: Arc<DensityGrid>,
+    /// Box the grid is stretched over
+    pub bounds: Aabb,
+    /// Extinction per world unit for a voxel value of 1
+    pub density: f64,
+}
+
+/// Depth of field look of the perspective camera
+pub struct LensSettings {
+    pub aperture_shape: ApertureShape,
+    pub cat_eye: f64,
+    pub vignetting: bool,
+    /// Lens shift in image widths and heights
+    pub shift: (f64, f64),
+    /// Plane of focus tilt and swing in radians
+    pub tilt: f64,
+    pub swing: f64,
 }
 
 pub enum ImageFormat {
@@ -20,7 +159,21 @@ pub enum ImageFormat {
     Unknown,
 }
 
-pub fn parse() -> ImageFormat {
+/// Luminance in cd/m² of a radiance of 1, that of the sky: a bright
+/// daylight sky which the default f/8, 1/125 and ISO 100 settings expose
+/// just like the camera without physical settings
+const DEFAULT_LUMINANCE_UNIT: f64 = 9600.0;
+
+const CAMERAS: &[&str] = &[
+    "perspective",
+    "orthographic",
+    "equirectangular",
+    "fisheye",
+    "cubemap",
+    "lens",
+];
+
+pub fn parse() -> UserData {
     let matches = App::new("Ray Tracer Test")
         .version("0.1.0")
         .author("KiKoS")
@@ -47,6 +200,303 @@ pub fn parse() -> ImageFormat {
                 .takes_value(true)
                 .help("Output file name"),
         )
+        .arg(
+            Arg::with_name("aov")
+                .long("aov")
+                .takes_value(true)
+                .possible_values(&["exr", "png"])
+                .help("Also write the render passes as a multi-layer exr or sibling png files"),
+        )
+        .arg(
+            Arg::with_name("denoise")
+                .long("denoise")
+                .help("Denoise the image using the albedo, normal and depth passes"),
+        )
+        .arg(
+            Arg::with_name("spectral")
+                .long("spectral")
+                .help("Render spectrally with hero wavelength sampling, showing dispersion"),
+        )
+        .arg(
+            Arg::with_name("adaptive")
+                .long("adaptive")
+                .takes_value(true)
+                .help("Keep sampling pixels until their relative error gets below this threshold"),
+        )
+        .arg(
+            Arg::with_name("min-samples")
+                .long("min-samples")
+                .takes_value(true)
+                .requires("adaptive")
+                .help("Samples taken in every pixel before checking its error"),
+        )
+        .arg(
+            Arg::with_name("max-samples")
+                .long("max-samples")
+                .takes_value(true)
+                .requires("adaptive")
+                .help("Maximum samples per pixel, defaults to the fixed sample count"),
+        )
+        .arg(
+            Arg::with_name("heatmap")
+                .long("heatmap")
+                .takes_value(true)
+                .requires("adaptive")
+                .help("Write the per pixel sample count as a png heatmap"),
+        )
+        .arg(
+            Arg::with_name("sampler")
+                .long("sampler")
+                .takes_value(true)
+                .possible_values(&["independent", "stratified", "halton", "sobol"])
+                .help("Sample generator used for pixels, lens and bounces"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .help("Seed of the scene and of every pixel sample, same seed gives the same image"),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .long("filter")
+                .takes_value(true)
+                .possible_values(&["box", "tent", "gaussian", "mitchell", "lanczos"])
+                .help("Pixel reconstruction filter"),
+        )
+        .arg(
+            Arg::with_name("filter-radius")
+                .long("filter-radius")
+                .takes_value(true)
+                .help("Radius of the reconstruction filter in pixels"),
+        )
+        .arg(
+            Arg::with_name("camera")
+                .long("camera")
+                .takes_value(true)
+                .possible_values(CAMERAS)
+                .help("Camera projection, overriding the scene file"),
+        )
+        .arg(
+            Arg::with_name("scene")
+                .long("scene")
+                .takes_value(true)
+                .help("Scene description file setting the camera, 'option value' lines named after camera, view-width, fisheye-fov and fisheye-mapping"),
+        )
+        .arg(
+            Arg::with_name("lens-file")
+                .long("lens-file")
+                .takes_value(true)
+                .required_if("camera", "lens")
+                .help("Lens prescription of the lens camera, one 'radius thickness ior aperture' line per surface in mm"),
+        )
+        .arg(
+            Arg::with_name("frames")
+                .long("frames")
+                .takes_value(true)
+                .help("Renders frames a..b (both included) to numbered images like img.0001.png"),
+        )
+        .arg(
+            Arg::with_name("fps")
+                .long("fps")
+                .takes_value(true)
+                .help("Frames per second, the shutter of frame n opening at n / fps (default 24)"),
+        )
+        .arg(
+            Arg::with_name("camera-path")
+                .long("camera-path")
+                .takes_value(true)
+                .conflicts_with("turntable")
+                .help("Camera keyframes file, one 'frame from.x from.y from.z at.x at.y at.z vfov focus' line per key"),
+        )
+        .arg(
+            Arg::with_name("interpolation")
+                .long("interpolation")
+                .takes_value(true)
+                .requires("camera-path")
+                .possible_values(&["linear", "catmull-rom"])
+                .help("Interpolation between camera keyframes (default catmull-rom)"),
+        )
+        .arg(
+            Arg::with_name("turntable")
+                .long("turntable")
+                .takes_value(true)
+                .help("Orbits the camera around the look-at point in that many frames"),
+        )
+        .arg(
+            Arg::with_name("view-width")
+                .long("view-width")
+                .takes_value(true)
+                .help("Width of the area seen by the orthographic camera, in world units"),
+        )
+        .arg(
+            Arg::with_name("fisheye-fov")
+                .long("fisheye-fov")
+                .takes_value(true)
+                .help("Field of view of the fisheye image circle in degrees"),
+        )
+        .arg(
+            Arg::with_name("fisheye-mapping")
+                .long("fisheye-mapping")
+                .takes_value(true)
+                .possible_values(&["equidistant", "equisolid"])
+                .help("Fisheye lens projection"),
+        )
+        .arg(
+            Arg::with_name("stereo")
+                .long("stereo")
+                .takes_value(true)
+                .possible_values(&["sbs", "ou", "anaglyph"])
+                .help("Render both eyes side by side, over-under or as a red-cyan anaglyph"),
+        )
+        .arg(
+            Arg::with_name("interocular")
+                .long("interocular")
+                .takes_value(true)
+                .requires("stereo")
+                .help("Distance between the eyes in world units"),
+        )
+        .arg(
+            Arg::with_name("convergence")
+                .long("convergence")
+                .takes_value(true)
+                .requires("stereo")
+                .help("Distance of the zero parallax plane"),
+        )
+        .arg(
+            Arg::with_name("shutter-open")
+                .long("shutter-open")
+                .takes_value(true)
+                .help("Time at which the shutter opens, enables motion blur (default 0)"),
+        )
+        .arg(
+            Arg::with_name("shutter-close")
+                .long("shutter-close")
+                .takes_value(true)
+                .help("Time at which the shutter closes, enables motion blur (default 1)"),
+        )
+        .arg(
+            Arg::with_name("aperture-blades")
+                .long("aperture-blades")
+                .takes_value(true)
+                .conflicts_with("aperture-mask")
+                .help("Number of diaphragm blades, polygonal bokeh (default round)"),
+        )
+        .arg(
+            Arg::with_name("aperture-rotation")
+                .long("aperture-rotation")
+                .takes_value(true)
+                .requires("aperture-blades")
+                .help("Rotation of the diaphragm blades in degrees"),
+        )
+        .arg(
+            Arg::with_name("aperture-mask")
+                .long("aperture-mask")
+                .takes_value(true)
+                .help("Grayscale image of the aperture transmission"),
+        )
+        .arg(
+            Arg::with_name("cat-eye")
+                .long("cat-eye")
+                .takes_value(true)
+                .help("Cat's eye vignetting strength, lens barrel shift in aperture radii at the corners"),
+        )
+        .arg(
+            Arg::with_name("shift-x")
+                .long("shift-x")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("Horizontal lens shift in image widths"),
+        )
+        .arg(
+            Arg::with_name("shift-y")
+                .long("shift-y")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("Vertical lens shift in image heights, keeps verticals parallel with a level camera"),
+        )
+        .arg(
+            Arg::with_name("tilt")
+                .long("tilt")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("Plane of focus tilt in degrees, positive bringing its bottom closer"),
+        )
+        .arg(
+            Arg::with_name("swing")
+                .long("swing")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("Plane of focus swing in degrees, positive bringing its right side closer"),
+        )
+        .arg(
+            Arg::with_name("f-number")
+                .long("f-number")
+                .takes_value(true)
+                .help("Physical camera f-stop, enables the physical camera (default 8)"),
+        )
+        .arg(
+            Arg::with_name("shutter-speed")
+                .long("shutter-speed")
+                .takes_value(true)
+                .help("Physical camera exposure time in seconds like 1/125, enables the physical camera (default 1/125)"),
+        )
+        .arg(
+            Arg::with_name("iso")
+                .long("iso")
+                .takes_value(true)
+                .help("Physical camera sensitivity, enables the physical camera (default 100)"),
+        )
+        .arg(
+            Arg::with_name("luminance-unit")
+                .long("luminance-unit")
+                .takes_value(true)
+                .help("Luminance in cd/m² of a radiance of 1 for the physical camera, the sky being 1 (default 9600, a daylight sky that f/8, 1/125 and ISO 100 expose like the default camera)"),
+        )
+        .arg(
+            Arg::with_name("fog")
+                .long("fog")
+                .takes_value(true)
+                .help("Fill the scene with fog of this density per world unit"),
+        )
+        .arg(
+            Arg::with_name("fog-height")
+                .long("fog-height")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("Altitude of the top of the fog (default 2)"),
+        )
+        .arg(
+            Arg::with_name("fog-anisotropy")
+                .long("fog-anisotropy")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("Mean scattering cosine of the fog, positive scattering forward (default 0.3)"),
+        )
+        .arg(
+            Arg::with_name("volume")
+                .long("volume")
+                .takes_value(true)
+                .help("Density grid of a cloud or smoke: a 'nx ny nz' text line then the densities as little endian f32, x fastest"),
+        )
+        .arg(
+            Arg::with_name("volume-density")
+                .long("volume-density")
+                .takes_value(true)
+                .help("Extinction per world unit of a voxel value of 1 (default 1)"),
+        )
+        .arg(
+            Arg::with_name("volume-bounds")
+                .long("volume-bounds")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help("Box holding the volume as 'x0,y0,z0,x1,y1,z1' (default -4,0,-3,4,2,3)"),
+        )
+        .arg(
+            Arg::with_name("vignetting")
+                .long("vignetting")
+                .help("Darken the image borders following the cos^4 law"),
+        )
         .get_matches();
 
     let format = matches.value_of("format").unwrap_or("png");
@@ -54,7 +504,13 @@ pub fn parse() -> ImageFormat {
     let width = usize::from_str(matches.value_of("width").unwrap_or("400"))
         .expect("Width cannot be parsed! Should be a number");
 
-    match format {
+    let aov = match matches.value_of("aov") {
+        Some("exr") => Some(AovFormat::Exr),
+        Some("png") => Some(AovFormat::Png),
+        _ => None,
+    };
+
+    let format = match format {
         "png" => ImageFormat::PNG {
             width,
             filename: output.to_string(),
@@ -64,5 +520,321 @@ pub fn parse() -> ImageFormat {
             filename: output.to_string(),
         },
         _ => ImageFormat::Unknown,
+    };
+
+    let adaptive_threshold = matches.value_of("adaptive").map(|t| {
+        f64::from_str(t).expect("Adaptive threshold cannot be parsed! Should be a number")
+    });
+    let min_samples = usize::from_str(matches.value_of("min-samples").unwrap_or("16"))
+        .expect("Minimum samples cannot be parsed! Should be a number");
+    let max_samples = matches.value_of("max-samples").map(|m| {
+        usize::from_str(m).expect("Maximum samples cannot be parsed! Should be a number")
+    });
+
+    let sampler = match matches.value_of("sampler").unwrap_or("independent") {
+        "stratified" => SamplerKind::Stratified,
+        "halton" => SamplerKind::Halton,
+        "sobol" => SamplerKind::Sobol,
+        _ => SamplerKind::Independent,
+    };
+
+    let seed = u64::from_str(matches.value_of("seed").unwrap_or("0"))
+        .expect("Seed cannot be parsed! Should be a positive number");
+
+    let filter_kind = match matches.value_of("filter").unwrap_or("box") {
+        "tent" => FilterKind::Tent,
+        "gaussian" => FilterKind::Gaussian,
+        "mitchell" => FilterKind::Mitchell,
+        "lanczos" => FilterKind::Lanczos,
+        _ => FilterKind::Box,
+    };
+    let filter = match matches.value_of("filter-radius") {
+        Some(r) => Filter::new(
+            filter_kind,
+            f64::from_str(r).expect("Filter radius cannot be parsed! Should be a number"),
+        ),
+        None => Filter::with_default_radius(filter_kind),
+    };
+
+    let scene = match matches.value_of("scene") {
+        Some(path) => read_scene(path).expect("Scene file cannot be read!"),
+        None => HashMap::new(),
+    };
+    // The command line wins over the scene file
+    let camera_setting = |name: &str| {
+        matches
+            .value_of(name)
+            .or_else(|| scene.get(name).map(String::as_str))
+    };
+    let view_width = f64::from_str(camera_setting("view-width").unwrap_or("7"))
+        .expect("View width cannot be parsed! Should be a number");
+    let camera = match camera_setting("camera").unwrap_or("perspective") {
+        "orthographic" => CameraKind::Orthographic { view_width },
+        "equirectangular" => CameraKind::Equirectangular,
+        "fisheye" => CameraKind::Fisheye {
+            fov: f64::from_str(camera_setting("fisheye-fov").unwrap_or("180"))
+                .expect("Fisheye field of view cannot be parsed! Should be a number"),
+            mapping: match camera_setting("fisheye-mapping") {
+                Some("equisolid") => FisheyeMapping::Equisolid,
+                _ => FisheyeMapping::Equidistant,
+            },
+        },
+        "cubemap" => CameraKind::Cubemap,
+        "lens" => CameraKind::LensSystem,
+        _ => CameraKind::Perspective,
+    };
+    let lens_elements = matches
+        .value_of("lens-file")
+        .map(|path| read_prescription(path).expect("Lens prescription cannot be read!"));
+    // A lens camera picked by the scene file escapes clap's check
+    if let (CameraKind::LensSystem, None) = (camera, &lens_elements) {
+        clap::Error::with_description(
+            "The lens camera needs a --lens-file",
+            clap::ErrorKind::MissingRequiredArgument,
+        )
+        .exit();
+    }
+
+    let stereo_camera = matches!(camera, CameraKind::Perspective | CameraKind::Equirectangular);
+    if matches.is_present("stereo") && !stereo_camera {
+        clap::Error::with_description(
+            "Stereo needs a perspective or equirectangular camera",
+            clap::ErrorKind::ArgumentConflict,
+        )
+        .exit();
+    }
+    let stereo = matches.value_of("stereo").map(|layout| StereoSettings {
+        layout: match layout {
+            "ou" => StereoLayout::OverUnder,
+            "anaglyph" => StereoLayout::Anaglyph,
+            _ => StereoLayout::SideBySide,
+        },
+        interocular: f64::from_str(matches.value_of("interocular").unwrap_or("0.065"))
+            .expect("Interocular distance cannot be parsed! Should be a number"),
+        convergence: f64::from_str(matches.value_of("convergence").unwrap_or("10"))
+            .expect("Convergence distance cannot be parsed! Should be a number"),
+    });
+
+    let shutter = match (matches.value_of("shutter-open"), matches.value_of("shutter-close")) {
+        (None, None) => None,
+        (open, close) => Some(Shutter::new(
+            f64::from_str(open.unwrap_or("0"))
+                .expect("Shutter open time cannot be parsed! Should be a number"),
+            f64::from_str(close.unwrap_or("1"))
+                .expect("Shutter close time cannot be parsed! Should be a number"),
+        )),
+    };
+
+    let aperture_shape = match (
+        matches.value_of("aperture-blades"),
+        matches.value_of("aperture-mask"),
+    ) {
+        (Some(blades), _) => ApertureShape::Polygon {
+            blades: usize::from_str(blades)
+                .expect("Aperture blades cannot be parsed! Should be a number"),
+            rotation: degrees_to_radians(
+                f64::from_str(matches.value_of("aperture-rotation").unwrap_or("0"))
+                    .expect("Aperture rotation cannot be parsed! Should be a number"),
+            ),
+        },
+        (None, Some(mask)) => ApertureShape::Mask(Arc::new(
+            ApertureMask::from_image(mask).expect("Aperture mask cannot be read!"),
+        )),
+        (None, None) => ApertureShape::Circular,
+    };
+    let lens = LensSettings {
+        aperture_shape,
+        cat_eye: f64::from_str(matches.value_of("cat-eye").unwrap_or("0"))
+            .expect("Cat's eye strength cannot be parsed! Should be a number"),
+        vignetting: matches.is_present("vignetting"),
+        shift: (
+            f64::from_str(matches.value_of("shift-x").unwrap_or("0"))
+                .expect("Horizontal shift cannot be parsed! Should be a number"),
+            f64::from_str(matches.value_of("shift-y").unwrap_or("0"))
+                .expect("Vertical shift cannot be parsed! Should be a number"),
+        ),
+        tilt: degrees_to_radians(
+            f64::from_str(matches.value_of("tilt").unwrap_or("0"))
+                .expect("Tilt cannot be parsed! Should be a number"),
+        ),
+        swing: degrees_to_radians(
+            f64::from_str(matches.value_of("swing").unwrap_or("0"))
+                .expect("Swing cannot be parsed! Should be a number"),
+        ),
+    };
+
+    let physical = match (
+        matches.value_of("f-number"),
+        matches.value_of("shutter-speed"),
+        matches.value_of("iso"),
+    ) {
+        (None, None, None) => None,
+        (f_number, shutter_speed, iso) => Some(PhysicalCamera::new(
+            f64::from_str(f_number.unwrap_or("8"))
+                .expect("F-number cannot be parsed! Should be a number"),
+            parse_fraction(shutter_speed.unwrap_or("1/125"))
+                .expect("Shutter speed cannot be parsed! Should be a number or a fraction"),
+            f64::from_str(iso.unwrap_or("100")).expect("ISO cannot be parsed! Should be a number"),
+        )),
+    };
+    let luminance_unit = matches.value_of("luminance-unit").map_or(DEFAULT_LUMINANCE_UNIT, |l| {
+        f64::from_str(l).expect("Luminance unit cannot be parsed! Should be a number")
+    });
+
+    let fog = matches.value_of("fog").map(|density| FogSettings {
+        density: f64::from_str(density).expect("Fog density cannot be parsed! Should be a number"),
+        height: f64::from_str(matches.value_of("fog-height").unwrap_or("2"))
+            .expect("Fog height cannot be parsed! Should be a number"),
+        anisotropy: f64::from_str(matches.value_of("fog-anisotropy").unwrap_or("0.3"))
+            .expect("Fog anisotropy cannot be parsed! Should be a number"),
+    });
+
+    let volume = matches.value_of("volume").map(|path| {
+        let grid = DensityGrid::from_file(path).expect("Density grid cannot be read!");
+        let corners = matches
+            .value_of("volume-bounds")
+            .unwrap_or("-4,0,-3,4,2,3")
+            .split(',')
+            .map(f64::from_str)
+            .collect::<Result<Vec<f64>, _>>()
+            .ok()
+            .filter(|c| c.len() == 6)
+            .expect("Volume bounds cannot be parsed! Should be 6 numbers like -1,0,-1,1,2,1");
+        VolumeSettings {
+            grid: Arc::new(grid),
+            bounds: Aabb::new(
+                Point3::with_values(corners[0], corners[1], corners[2]),
+                Point3::with_values(corners[3], corners[4], corners[5]),
+            ),
+            density: f64::from_str(matches.value_of("volume-density").unwrap_or("1"))
+                .expect("Volume density cannot be parsed! Should be a number"),
+        }
+    });
+
+    let interpolation = match matches.value_of("interpolation") {
+        Some("linear") => Interpolation::Linear,
+        _ => Interpolation::CatmullRom,
+    };
+    let camera_path = matches.value_of("camera-path").map(|path| {
+        CameraPath::from_file(path, interpolation).expect("Camera path cannot be read!")
+    });
+    let turntable = matches.value_of("turntable").map(|frames| {
+        usize::from_str(frames)
+            .ok()
+            .filter(|frames| *frames > 0)
+            .expect("Turntable frames cannot be parsed! Should be a positive number")
+    });
+    let frames = matches.value_of("frames").map(|frames| {
+        parse_range(frames).expect("Frames cannot be parsed! Should be like 1..100")
+    });
+    let fps = f64::from_str(matches.value_of("fps").unwrap_or("24"))
+        .expect("Frame rate cannot be parsed! Should be a number");
+
+    UserData {
+        camera,
+        lens_elements,
+        camera_path,
+        turntable,
+        frames,
+        fps,
+        stereo,
+        shutter,
+        lens,
+        physical,
+        luminance_unit,
+        format,
+        aov,
+        denoise: matches.is_present("denoise"),
+        spectral: matches.is_present("spectral"),
+        fog,
+        volume,
+        adaptive_threshold,
+        min_samples,
+        max_samples,
+        heatmap: matches.value_of("heatmap").map(|h| h.to_string()),
+        sampler,
+        seed,
+        filter,
+    }
+}
+
+/// Reads the camera settings of a scene file, one 'option value' line
+/// per setting named after the command line option, `#` starting comments
+fn read_scene<P: AsRef<Path>>(path: P) -> std::io::Result<HashMap<String, String>> {
+    parse_scene(&fs::read_to_string(path)?).map_err(|line| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid scene line: {}", line),
+        )
+    })
+}
+
+fn parse_scene(text: &str) -> Result<HashMap<String, String>, String> {
+    let mut settings = HashMap::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let valid = match words.as_slice() {
+            ["camera", kind] => CAMERAS.contains(kind),
+            ["fisheye-mapping", mapping] => ["equidistant", "equisolid"].contains(mapping),
+            ["view-width", v] | ["fisheye-fov", v] => f64::from_str(v).is_ok(),
+            _ => false,
+        };
+        if !valid {
+            return Err(line.to_string());
+        }
+        settings.insert(words[0].to_string(), words[1].to_string());
+    }
+    Ok(settings)
+}
+
+/// Number written as a decimal or as a fraction like 1/125
+fn parse_fraction(s: &str) -> Option<f64> {
+    match s.find('/') {
+        Some(index) => match (f64::from_str(&s[..index]), f64::from_str(&s[index + 1..])) {
+            (Ok(n), Ok(d)) => Some(n / d),
+            _ => None,
+        },
+        None => f64::from_str(s).ok(),
     }
-}
\ No newline at end of file
+}
+
+/// Inclusive range written as a..b, or a single number
+fn parse_range(s: &str) -> Option<(i64, i64)> {
+    match s.find("..") {
+        Some(index) => match (i64::from_str(&s[..index]), i64::from_str(&s[index + 2..])) {
+            (Ok(a), Ok(b)) if a <= b => Some((a, b)),
+            _ => None,
+        },
+        None => i64::from_str(s).ok().map(|frame| (frame, frame)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_scene, DEFAULT_LUMINANCE_UNIT};
+    use crate::libcore::camera::PhysicalCamera;
+
+    #[test]
+    fn default_physical_settings_keep_the_exposure() {
+        let camera = PhysicalCamera::new(8.0, 1.0 / 125.0, 100.0);
+        assert!((camera.exposure() * DEFAULT_LUMINANCE_UNIT - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn scene_files_set_the_camera() {
+        let scene =
+            parse_scene("# Plan view\ncamera orthographic\n\nview-width 12 # meters\n").unwrap();
+        assert_eq!(scene["camera"], "orthographic");
+        assert_eq!(scene["view-width"], "12");
+        assert_eq!(scene.len(), 2);
+
+        assert!(parse_scene("camera isometric").is_err());
+        assert!(parse_scene("view-width wide").is_err());
+        assert!(parse_scene("view-width 12 15").is_err());
+        assert!(parse_scene("lookfrom 0 0 0").is_err());
+    }
+}